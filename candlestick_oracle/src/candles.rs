@@ -0,0 +1,232 @@
+use alloy::primitives::{B256, U256};
+use oracle_core::{format_token_amount, AmountStyle, Price};
+
+use crate::{CandlestickData, SwapVolume};
+
+/// One OHLC interval with exact prices and raw volumes, only turned into
+/// [`CandlestickData`] for output so rolled-up candles lose no precision.
+#[derive(Debug, Clone)]
+pub struct Candle {
+    /// Start of the interval in unix seconds
+    pub timestamp: i64,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: SwapVolume,
+    /// Blocks and transactions of the first and last event in the interval
+    pub first_block: u64,
+    pub last_block: u64,
+    pub first_tx: Option<B256>,
+    pub last_tx: Option<B256>,
+}
+
+impl Candle {
+    pub fn to_data(&self, base_decimals: u8, quote_decimals: u8) -> CandlestickData {
+        CandlestickData {
+            timestamp: self.timestamp * 1000, // Convert to milliseconds
+            open: format!("{:.32}", self.open),
+            high: format!("{:.32}", self.high),
+            low: format!("{:.32}", self.low),
+            close: format!("{:.32}", self.close),
+            volume: amount(self.volume.base, base_decimals),
+            quote_volume: amount(self.volume.quote, quote_decimals),
+            vwap: self
+                .vwap(base_decimals, quote_decimals)
+                .map(|vwap| format!("{:.32}", vwap)),
+            trades: self.volume.trades,
+            buy_volume: amount(self.volume.buy, base_decimals),
+            sell_volume: amount(self.volume.sell, base_decimals),
+            first_block: Some(self.first_block),
+            last_block: Some(self.last_block),
+            first_tx: self.first_tx,
+            last_tx: self.last_tx,
+        }
+    }
+
+    /// Quote volume per base volume, exact like the OHLC prices; `None` if
+    /// nothing was traded.
+    pub fn vwap(&self, base_decimals: u8, quote_decimals: u8) -> Option<Price> {
+        if self.volume.base.is_zero() {
+            return None;
+        }
+        Some(Price::from_amounts(
+            self.volume.base,
+            base_decimals,
+            self.volume.quote,
+            quote_decimals,
+        ))
+    }
+}
+
+fn amount(value: U256, decimals: u8) -> String {
+    format_token_amount(value, decimals, AmountStyle::Fixed(decimals as usize))
+}
+
+/// Aggregates time-ordered candles of a finer interval into candles of
+/// `interval_minutes`, which the finer interval must divide.
+pub fn roll_up(candles: &[Candle], interval_minutes: u64) -> Vec<Candle> {
+    let interval = interval_minutes as i64 * 60;
+    let mut rolled: Vec<Candle> = Vec::new();
+
+    for candle in candles {
+        let timestamp = candle.timestamp.div_euclid(interval) * interval;
+
+        match rolled.last_mut() {
+            Some(current) if current.timestamp == timestamp => {
+                current.high = current.high.clone().max(candle.high.clone());
+                current.low = current.low.clone().min(candle.low.clone());
+                current.close = candle.close.clone();
+                current.volume.add(&candle.volume);
+                current.last_block = candle.last_block;
+                current.last_tx = candle.last_tx;
+            }
+            _ => rolled.push(Candle {
+                timestamp,
+                ..candle.clone()
+            }),
+        }
+    }
+
+    rolled
+}
+
+/// Parses an interval such as `15`, `15m`, `4h` or `1d` into minutes.
+pub fn parse_interval(value: &str) -> Result<u64, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "m"),
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid interval `{}`", value))?;
+    let multiplier = match unit {
+        "m" => 1,
+        "h" => 60,
+        "d" => 24 * 60,
+        _ => return Err(format!("unknown unit in interval `{}`", value)),
+    };
+
+    match number.checked_mul(multiplier) {
+        Some(0) => Err("interval must be at least one minute".to_string()),
+        Some(minutes) => Ok(minutes),
+        None => Err(format!("interval `{}` is too long", value)),
+    }
+}
+
+/// Shortest label for an interval, e.g. `90m`, `4h` or `1d`.
+pub fn interval_label(minutes: u64) -> String {
+    if minutes.is_multiple_of(24 * 60) {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes.is_multiple_of(60) {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(value: u64) -> Price {
+        Price::from_ratio(U256::from(value), U256::from(1u8))
+    }
+
+    /// A candle starting at `minute` with `[open, high, low, close]` and one
+    /// trade of `volume` base tokens in block `block`.
+    fn candle(minute: i64, ohlc: [u64; 4], volume: u64, block: u64) -> Candle {
+        let [open, high, low, close] = ohlc;
+        Candle {
+            timestamp: minute * 60,
+            open: price(open),
+            high: price(high),
+            low: price(low),
+            close: price(close),
+            volume: SwapVolume {
+                base: U256::from(volume),
+                quote: U256::from(volume * close),
+                buy: U256::from(volume),
+                sell: U256::ZERO,
+                trades: 1,
+            },
+            first_block: block,
+            last_block: block,
+            first_tx: Some(B256::repeat_byte(block as u8)),
+            last_tx: Some(B256::repeat_byte(block as u8)),
+        }
+    }
+
+    #[test]
+    fn rolls_up_one_minute_candles() {
+        let minutes = [
+            candle(0, [10, 12, 9, 11], 1, 1),
+            candle(1, [11, 15, 11, 14], 2, 2),
+            candle(4, [14, 14, 8, 9], 3, 4),
+            candle(5, [9, 10, 9, 10], 4, 5),
+        ];
+
+        let rolled = roll_up(&minutes, 5);
+        assert_eq!(rolled.len(), 2);
+
+        let first = &rolled[0];
+        assert_eq!(first.timestamp, 0);
+        assert_eq!(first.open, price(10));
+        assert_eq!(first.high, price(15));
+        assert_eq!(first.low, price(8));
+        assert_eq!(first.close, price(9));
+        assert_eq!(first.volume.base, U256::from(6u8));
+        assert_eq!(first.volume.quote, U256::from(11u8 + 28 + 27));
+        assert_eq!(first.volume.trades, 3);
+        assert_eq!(first.first_block, 1);
+        assert_eq!(first.last_block, 4);
+        assert_eq!(first.first_tx, Some(B256::repeat_byte(1)));
+        assert_eq!(first.last_tx, Some(B256::repeat_byte(4)));
+
+        let second = &rolled[1];
+        assert_eq!(second.timestamp, 300);
+        assert_eq!(second.open, price(9));
+        assert_eq!(second.first_block, 5);
+        assert_eq!(second.last_block, 5);
+    }
+
+    #[test]
+    fn rolls_up_rolled_up_candles() {
+        let minutes: Vec<Candle> = (0..60)
+            .map(|minute| {
+                let value = 100 + minute as u64;
+                candle(minute * 2, [value, value + 1, value - 1, value], 1, value)
+            })
+            .collect();
+
+        // 1m → 5m → 1h matches rolling 1m straight up to 1h
+        let hours = roll_up(&roll_up(&minutes, 5), 60);
+        let direct = roll_up(&minutes, 60);
+        assert_eq!(hours.len(), 2);
+        assert_eq!(direct.len(), 2);
+
+        for (hour, direct) in hours.iter().zip(&direct) {
+            assert_eq!(hour.timestamp, direct.timestamp);
+            assert_eq!(hour.open, direct.open);
+            assert_eq!(hour.high, direct.high);
+            assert_eq!(hour.low, direct.low);
+            assert_eq!(hour.close, direct.close);
+            assert_eq!(hour.volume.base, direct.volume.base);
+            assert_eq!(hour.volume.trades, direct.volume.trades);
+            assert_eq!(hour.last_block, direct.last_block);
+        }
+
+        // Minutes 0..58 in the first hour, 60..118 in the second
+        let first = &hours[0];
+        assert_eq!(first.open, price(100));
+        assert_eq!(first.high, price(130));
+        assert_eq!(first.low, price(99));
+        assert_eq!(first.close, price(129));
+        assert_eq!(first.volume.trades, 30);
+        assert_eq!(first.first_block, 100);
+        assert_eq!(first.last_block, 129);
+        assert_eq!(hours[1].timestamp, 3600);
+        assert_eq!(hours[1].last_block, 159);
+    }
+}
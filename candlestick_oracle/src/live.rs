@@ -0,0 +1,699 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy::{
+    primitives::B256,
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::{Filter, Log},
+    sol_types::SolEvent,
+};
+use anyhow::Result;
+use futures_util::StreamExt;
+use oracle_core::{
+    Backoff, BlockTimestamps, ChainConfig, Config, Finality, LogFetcher, LogTracker, LogUpdate,
+    Pool, PoolConfig, UniswapV2Pair,
+};
+use serde::Serialize;
+
+use crate::{
+    candles::{interval_label, Candle},
+    create_candlesticks, parse_sync_event, CandlestickData, ChainBackfill, Cli, PriceData,
+    SwapVolume,
+};
+
+/// A change to one resolution's candle.
+#[derive(Debug, Clone)]
+pub enum CandleUpdate {
+    /// The open candle changed and may change again.
+    Provisional(Candle),
+    /// The interval is over; the candle will not change anymore.
+    Final(Candle),
+}
+
+impl CandleUpdate {
+    pub fn candle(&self) -> &Candle {
+        match self {
+            CandleUpdate::Provisional(candle) | CandleUpdate::Final(candle) => candle,
+        }
+    }
+
+    pub fn status(&self) -> &'static str {
+        match self {
+            CandleUpdate::Provisional(_) => "provisional",
+            CandleUpdate::Final(_) => "final",
+        }
+    }
+}
+
+/// What [`follow`] reports while the chain moves on.
+#[derive(Debug, Clone)]
+pub enum FollowEvent {
+    /// The candle of the given interval (in minutes) changed.
+    Candle(u64, CandleUpdate),
+    /// A swap reached the required confirmations and was added to the
+    /// candles.
+    Trade(PriceData),
+}
+
+/// Outcome of [`CandleBuilder::retract`].
+#[derive(Debug, Clone)]
+pub enum Retraction {
+    /// The open candle changed; see [`CandleBuilder::open_candle`].
+    Updated,
+    /// The event was not part of any candle of this resolution.
+    Unaffected,
+    /// The event belongs to a candle that was already emitted as final.
+    AlreadyFinal,
+}
+
+/// Keeps the open candle of one resolution up to date.
+///
+/// The price points of the open candle are kept so a retracted event can be
+/// taken out again; they are dropped once the candle is final.
+#[derive(Debug)]
+pub struct CandleBuilder {
+    interval_minutes: u64,
+    points: Vec<PriceData>,
+}
+
+impl CandleBuilder {
+    /// Continues from `history`, whose last interval is still open unless the
+    /// chain already passed `history_end` (unix seconds) beyond it.
+    pub fn new(interval_minutes: u64, history: &[PriceData], history_end: i64) -> Self {
+        let mut builder = Self {
+            interval_minutes,
+            points: Vec::new(),
+        };
+
+        if let Some(last) = history.last() {
+            let start = builder.interval_start(last);
+            if start + builder.interval_seconds() > history_end {
+                builder.points = history
+                    .iter()
+                    .filter(|data| builder.interval_start(data) == start)
+                    .cloned()
+                    .collect();
+            }
+        }
+
+        builder
+    }
+
+    pub fn interval_minutes(&self) -> u64 {
+        self.interval_minutes
+    }
+
+    /// Adds one price point, closing the open candle first if the point
+    /// belongs to a later interval.
+    pub fn push(&mut self, data: PriceData) -> Vec<CandleUpdate> {
+        let mut updates = Vec::new();
+
+        let start = self.interval_start(&data);
+        if let Some(open_start) = self.open_start() {
+            if start > open_start {
+                updates.extend(self.close());
+            }
+        }
+
+        self.points.push(data);
+        updates.extend(self.open_candle().map(CandleUpdate::Provisional));
+        updates
+    }
+
+    /// Closes the open candle once the chain's time reached the end of its
+    /// interval, even if no further event arrived.
+    pub fn advance(&mut self, timestamp: i64) -> Option<CandleUpdate> {
+        let open_start = self.open_start()?;
+        if timestamp < open_start + self.interval_seconds() {
+            return None;
+        }
+        self.close()
+    }
+
+    /// Takes a reorged-out Sync event back out of the open candle.
+    pub fn retract(&mut self, block_number: u64, log_index: u64) -> Retraction {
+        let Some(position) = self
+            .points
+            .iter()
+            .position(|data| data.block_number == block_number && data.log_index == log_index)
+        else {
+            let before_open_candle = self
+                .points
+                .first()
+                .is_none_or(|first| block_number < first.block_number);
+            return if before_open_candle {
+                Retraction::AlreadyFinal
+            } else {
+                Retraction::Unaffected
+            };
+        };
+
+        self.points.remove(position);
+        Retraction::Updated
+    }
+
+    fn close(&mut self) -> Option<CandleUpdate> {
+        let candle = self.open_candle();
+        self.points.clear();
+        candle.map(CandleUpdate::Final)
+    }
+
+    /// The candle of the current interval, if it has any events yet.
+    pub fn open_candle(&self) -> Option<Candle> {
+        create_candlesticks(&self.points, self.interval_minutes).pop()
+    }
+
+    fn open_start(&self) -> Option<i64> {
+        self.points.first().map(|data| self.interval_start(data))
+    }
+
+    fn interval_seconds(&self) -> i64 {
+        self.interval_minutes as i64 * 60
+    }
+
+    fn interval_start(&self, data: &PriceData) -> i64 {
+        let interval = self.interval_seconds();
+        data.timestamp.timestamp().div_euclid(interval) * interval
+    }
+}
+
+/// One line of `--follow` output.
+#[derive(Serialize)]
+struct UpdateLine {
+    interval: String,
+    status: &'static str,
+    candle: CandlestickData,
+}
+
+/// Subscribes to the pair's Sync and Swap events after the backfill and hands
+/// every candle update and new trade to `on_event`, reconnecting whenever
+/// the subscription drops.
+pub async fn follow<F>(
+    cli: &Cli,
+    config: &Config,
+    pool_config: &PoolConfig,
+    timestamps: Arc<BlockTimestamps>,
+    backfill: ChainBackfill,
+    mut on_event: F,
+) -> Result<()>
+where
+    F: FnMut(&Pool, FollowEvent) -> Result<()>,
+{
+    let chain = config.chain(pool_config);
+
+    let provider = ProviderBuilder::new().connect_http(chain.rpc_url.parse()?);
+    let history_end = timestamps
+        .resolve(&provider, [backfill.to_block])
+        .await?
+        .get(&backfill.to_block)
+        .map_or(0, |&timestamp| timestamp as i64);
+    let live = LiveCandles::new(
+        cli.intervals
+            .iter()
+            .map(|&interval_minutes| {
+                CandleBuilder::new(interval_minutes, &backfill.price_data, history_end)
+            })
+            .collect(),
+    );
+
+    let filter = Filter::new()
+        .address(backfill.pool.address)
+        .event_signature(vec![
+            UniswapV2Pair::Sync::SIGNATURE_HASH,
+            UniswapV2Pair::Swap::SIGNATURE_HASH,
+        ]);
+
+    let mut follower = Follower {
+        cli,
+        chain,
+        timestamps,
+        pool: &backfill.pool,
+        filter,
+        finality: Finality::new(cli.confirmations),
+        tracker: LogTracker::new(Finality::new(cli.confirmations)),
+        live,
+        backfill_end: backfill.to_block,
+    };
+    let mut backoff = Backoff::new();
+
+    // The tracker and the builders outlive every connection, so each
+    // reconnect resumes right after the last block that was processed
+    loop {
+        let ended = follower.session(&mut backoff, &mut on_event).await;
+        backoff
+            .retry(format!("⚠️  {}", pool_config.name), ended)
+            .await;
+    }
+}
+
+/// State of [`follow`] that survives reconnects.
+struct Follower<'a> {
+    cli: &'a Cli,
+    chain: &'a ChainConfig,
+    timestamps: Arc<BlockTimestamps>,
+    pool: &'a Pool,
+    filter: Filter,
+    finality: Finality,
+    tracker: LogTracker,
+    live: LiveCandles,
+    /// Last block of the backfill, where the first session picks up
+    backfill_end: u64,
+}
+
+impl Follower<'_> {
+    /// One WebSocket session: subscribes, fetches anything missed since the
+    /// last block seen (or since the backfill), then follows the
+    /// subscription until it ends.
+    async fn session<F>(&mut self, backoff: &mut Backoff, on_event: &mut F) -> Result<()>
+    where
+        F: FnMut(&Pool, FollowEvent) -> Result<()>,
+    {
+        let ws = WsConnect::new(self.chain.ws_url());
+        let provider = ProviderBuilder::new().connect_ws(ws).await?;
+
+        // Subscribe before fetching what was missed so nothing falls between
+        // the two; the tracker drops logs seen twice
+        let mut logs = provider.subscribe_logs(&self.filter).await?.into_stream();
+        let mut heads = provider.subscribe_blocks().await?.into_stream();
+        backoff.reset();
+
+        let from_block = match self.tracker.head() {
+            0 => self.backfill_end + 1,
+            head => head,
+        };
+        let head = provider.get_block_number().await?;
+        if head >= from_block {
+            let missed = LogFetcher::new()
+                .chunk_size(self.cli.log_chunk_blocks)
+                .concurrency(self.cli.log_concurrency)
+                .fetch(&provider, &self.filter, from_block, head)
+                .await?;
+
+            let updates: Vec<LogUpdate> = missed
+                .into_iter()
+                .flat_map(|log| self.tracker.push(log))
+                .collect();
+            self.apply(&provider, updates, on_event).await?;
+        }
+
+        loop {
+            let updates = tokio::select! {
+                log = logs.next() => match log {
+                    Some(log) => self.tracker.push(log),
+                    None => return Ok(()),
+                },
+                head = heads.next() => match head {
+                    Some(head) => {
+                        self.timestamps.record(head.number, head.timestamp);
+                        self.tracker.advance(head.number, head.hash)
+                    }
+                    None => return Ok(()),
+                },
+            };
+
+            self.apply(&provider, updates, on_event).await?;
+        }
+    }
+
+    /// Feeds tracker updates into the candles, then pushes what is complete
+    /// and closes what is over.
+    async fn apply<P, F>(
+        &mut self,
+        provider: &P,
+        updates: Vec<LogUpdate>,
+        on_event: &mut F,
+    ) -> Result<()>
+    where
+        P: Provider,
+        F: FnMut(&Pool, FollowEvent) -> Result<()>,
+    {
+        apply(
+            provider,
+            &self.timestamps,
+            self.pool,
+            &mut self.live,
+            updates,
+            on_event,
+        )
+        .await?;
+        self.live.settle(self.pool, self.tracker.head(), on_event)?;
+
+        // Candles close on chain time: once the newest final block is past
+        // their interval, no further event can land in them
+        let final_block = self.finality.final_block(self.tracker.head());
+        if let Some(timestamp) = self.timestamps.get(final_block) {
+            self.live.advance(self.pool, timestamp as i64, on_event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the timestamps of emitted logs and feeds them to `live`.
+async fn apply<P, F>(
+    provider: &P,
+    timestamps: &BlockTimestamps,
+    pool: &Pool,
+    live: &mut LiveCandles,
+    updates: Vec<LogUpdate>,
+    on_event: &mut F,
+) -> Result<()>
+where
+    P: Provider,
+    F: FnMut(&Pool, FollowEvent) -> Result<()>,
+{
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let block_timestamps = timestamps
+        .resolve(
+            provider,
+            updates.iter().filter_map(|update| match update {
+                LogUpdate::Emit(log) => log.block_number,
+                LogUpdate::Retract(_) => None,
+            }),
+        )
+        .await?;
+
+    live.apply(pool, updates, &block_timestamps, on_event)
+}
+
+/// The candle builders of every resolution and the Sync still waiting for
+/// the Swap of its transaction.
+///
+/// Without confirmations the tracker hands out logs one at a time, so a
+/// Sync is held across batches until its transaction can have no more logs.
+pub struct LiveCandles {
+    builders: Vec<CandleBuilder>,
+    pending: Option<(Option<B256>, PriceData)>,
+}
+
+impl LiveCandles {
+    pub fn new(builders: Vec<CandleBuilder>) -> Self {
+        Self {
+            builders,
+            pending: None,
+        }
+    }
+
+    /// Feeds emitted and retracted logs into every builder.
+    pub fn apply<F>(
+        &mut self,
+        pool: &Pool,
+        updates: Vec<LogUpdate>,
+        block_timestamps: &HashMap<u64, u64>,
+        on_event: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Pool, FollowEvent) -> Result<()>,
+    {
+        for update in updates {
+            match update {
+                LogUpdate::Emit(log) => match log.topic0() {
+                    Some(&UniswapV2Pair::Sync::SIGNATURE_HASH) => {
+                        self.flush(pool, on_event)?;
+                        if let Ok(data) = parse_sync_event(&log, block_timestamps, pool) {
+                            self.pending = Some((log.transaction_hash, data));
+                        }
+                    }
+                    // A pair emits Sync right before Swap in the same
+                    // transaction; Syncs from mints and burns have no Swap
+                    Some(&UniswapV2Pair::Swap::SIGNATURE_HASH) => match &mut self.pending {
+                        Some((tx_hash, data)) if *tx_hash == log.transaction_hash => {
+                            data.volume.add(&swap_volume(pool, &log));
+                        }
+                        _ => self.flush(pool, on_event)?,
+                    },
+                    _ => {}
+                },
+                // The Sync of a swap is retracted along with it and carries its
+                // volume, so only Syncs need to be taken out
+                LogUpdate::Retract(log) => {
+                    if log.topic0() != Some(&UniswapV2Pair::Sync::SIGNATURE_HASH) {
+                        continue;
+                    }
+                    let block_number = log.block_number.unwrap_or_default();
+                    let log_index = log.log_index.unwrap_or_default();
+
+                    // A held Sync never reached the builders
+                    let held = self.pending.as_ref().is_some_and(|(_, data)| {
+                        data.block_number == block_number && data.log_index == log_index
+                    });
+                    if held {
+                        self.pending = None;
+                        continue;
+                    }
+
+                    for builder in self.builders.iter_mut() {
+                        match builder.retract(block_number, log_index) {
+                            Retraction::Updated => {
+                                if let Some(candle) = builder.open_candle() {
+                                    let update = CandleUpdate::Provisional(candle);
+                                    on_event(
+                                        pool,
+                                        FollowEvent::Candle(builder.interval_minutes(), update),
+                                    )?;
+                                }
+                            }
+                            Retraction::Unaffected => {}
+                            Retraction::AlreadyFinal => eprintln!(
+                                "⚠️  Sync in block {} was reorged out after its {} candle was final; \
+                                 use --confirmations to avoid this",
+                                block_number,
+                                interval_label(builder.interval_minutes())
+                            ),
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the held Sync once the chain is past its block, as every log
+    /// of its transaction has been delivered by then.
+    pub fn settle<F>(&mut self, pool: &Pool, head: u64, on_event: &mut F) -> Result<()>
+    where
+        F: FnMut(&Pool, FollowEvent) -> Result<()>,
+    {
+        let complete = self
+            .pending
+            .as_ref()
+            .is_some_and(|(_, data)| data.block_number < head);
+        if complete {
+            self.flush(pool, on_event)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the candles whose interval ended before `timestamp`.
+    pub fn advance<F>(&mut self, pool: &Pool, timestamp: i64, on_event: &mut F) -> Result<()>
+    where
+        F: FnMut(&Pool, FollowEvent) -> Result<()>,
+    {
+        for builder in &mut self.builders {
+            if let Some(update) = builder.advance(timestamp) {
+                on_event(
+                    pool,
+                    FollowEvent::Candle(builder.interval_minutes(), update),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush<F>(&mut self, pool: &Pool, on_event: &mut F) -> Result<()>
+    where
+        F: FnMut(&Pool, FollowEvent) -> Result<()>,
+    {
+        match self.pending.take() {
+            Some((_, data)) => push(pool, &mut self.builders, data, on_event),
+            None => Ok(()),
+        }
+    }
+}
+
+fn push<F>(
+    pool: &Pool,
+    builders: &mut [CandleBuilder],
+    data: PriceData,
+    on_event: &mut F,
+) -> Result<()>
+where
+    F: FnMut(&Pool, FollowEvent) -> Result<()>,
+{
+    // Syncs from mints and burns move no volume and are not trades
+    if data.volume.trades > 0 {
+        on_event(pool, FollowEvent::Trade(data.clone()))?;
+    }
+
+    for builder in builders {
+        for update in builder.push(data.clone()) {
+            on_event(
+                pool,
+                FollowEvent::Candle(builder.interval_minutes(), update),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn swap_volume(pool: &Pool, log: &Log) -> SwapVolume {
+    match UniswapV2Pair::Swap::decode_log_data(log.data()) {
+        Ok(swap) => SwapVolume::from_swap(pool, &swap),
+        Err(_) => SwapVolume::default(),
+    }
+}
+
+/// Prints a candle update as one line of `--follow` output; trades are
+/// already part of the candles printed.
+pub fn print_update(pool: &Pool, event: FollowEvent) -> Result<()> {
+    let FollowEvent::Candle(interval_minutes, update) = event else {
+        return Ok(());
+    };
+
+    let line = UpdateLine {
+        interval: interval_label(interval_minutes),
+        status: update.status(),
+        candle: update
+            .candle()
+            .to_data(pool.base().decimals, pool.quote().decimals),
+    };
+    println!("{}", serde_json::to_string(&line)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{aliases::U112, Address, LogData, U256};
+    use oracle_core::Orientation;
+
+    use super::*;
+    use crate::test_support::pool;
+
+    const BLOCK: u64 = 100;
+    const TIMESTAMP: u64 = 1_700_000_000;
+
+    fn log(tx: u8, log_index: u64, data: LogData) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address: pool(Orientation::Token0Base).address,
+                data,
+            },
+            block_hash: Some(B256::repeat_byte(0xbb)),
+            block_number: Some(BLOCK),
+            transaction_hash: Some(B256::repeat_byte(tx)),
+            log_index: Some(log_index),
+            ..Log::default()
+        }
+    }
+
+    fn sync(tx: u8, log_index: u64) -> LogUpdate {
+        let event = UniswapV2Pair::Sync {
+            reserve0: U112::from(2_000u64),
+            reserve1: U112::from(1_000u64),
+        };
+        LogUpdate::Emit(log(tx, log_index, event.encode_log_data()))
+    }
+
+    fn swap(tx: u8, log_index: u64) -> LogUpdate {
+        let event = UniswapV2Pair::Swap {
+            sender: Address::ZERO,
+            amount0In: U256::from(10u64),
+            amount1In: U256::ZERO,
+            amount0Out: U256::ZERO,
+            amount1Out: U256::from(5u64),
+            to: Address::ZERO,
+        };
+        LogUpdate::Emit(log(tx, log_index, event.encode_log_data()))
+    }
+
+    /// Runs `batches` through one [`LiveCandles`], then lets the chain move
+    /// past [`BLOCK`], and returns every event reported.
+    fn run(batches: Vec<Vec<LogUpdate>>) -> Vec<FollowEvent> {
+        let pool = pool(Orientation::Token0Base);
+        let block_timestamps = HashMap::from([(BLOCK, TIMESTAMP)]);
+        let mut live = LiveCandles::new(vec![CandleBuilder::new(1, &[], 0)]);
+        let mut events = Vec::new();
+        let mut on_event = |_: &Pool, event| {
+            events.push(event);
+            Ok(())
+        };
+
+        for batch in batches {
+            live.apply(&pool, batch, &block_timestamps, &mut on_event)
+                .unwrap();
+        }
+        live.settle(&pool, BLOCK + 1, &mut on_event).unwrap();
+        events
+    }
+
+    fn trades(events: &[FollowEvent]) -> Vec<&PriceData> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                FollowEvent::Trade(data) => Some(data),
+                FollowEvent::Candle(..) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn swap_in_a_later_batch_adds_volume() {
+        let events = run(vec![vec![sync(1, 0)], vec![swap(1, 1)]]);
+
+        let trades = trades(&events);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].volume.trades, 1);
+        assert_eq!(trades[0].volume.base, U256::from(10u64));
+        assert_eq!(trades[0].volume.quote, U256::from(5u64));
+        assert_eq!(trades[0].volume.sell, U256::from(10u64));
+
+        let Some(FollowEvent::Candle(1, update)) = events.last() else {
+            panic!("no candle update in {:?}", events);
+        };
+        assert_eq!(update.candle().volume.trades, 1);
+        assert_eq!(update.candle().volume.base, U256::from(10u64));
+    }
+
+    #[test]
+    fn sync_is_held_until_its_transaction_is_complete() {
+        let pool = pool(Orientation::Token0Base);
+        let block_timestamps = HashMap::from([(BLOCK, TIMESTAMP)]);
+        let mut live = LiveCandles::new(vec![CandleBuilder::new(1, &[], 0)]);
+        let mut count = 0;
+        let mut on_event = |_: &Pool, _| {
+            count += 1;
+            Ok(())
+        };
+
+        live.apply(&pool, vec![sync(1, 0)], &block_timestamps, &mut on_event)
+            .unwrap();
+        live.settle(&pool, BLOCK, &mut on_event).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn next_transaction_releases_the_held_sync() {
+        // A mint's Sync has no Swap; the swap after it is a separate trade
+        let events = run(vec![vec![sync(1, 0)], vec![sync(2, 1)], vec![swap(2, 2)]]);
+
+        let trades = trades(&events);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].tx_hash, Some(B256::repeat_byte(2)));
+    }
+
+    #[test]
+    fn retracted_held_sync_is_dropped() {
+        let LogUpdate::Emit(removed) = sync(1, 0) else {
+            unreachable!()
+        };
+        let events = run(vec![
+            vec![sync(1, 0)],
+            vec![swap(1, 1)],
+            vec![LogUpdate::Retract(removed)],
+        ]);
+
+        assert!(events.is_empty(), "{:?}", events);
+    }
+}
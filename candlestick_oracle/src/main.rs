@@ -1,163 +1,630 @@
 use alloy::{
-    primitives::{address, keccak256, Address, U256},
+    eips::BlockId,
+    primitives::{B256, U256},
     providers::{Provider, ProviderBuilder},
     rpc::types::{Filter, Log},
-    sol,
+    sol_types::SolEvent,
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
+use clap::{Parser, ValueEnum};
+use oracle_core::{
+    calculate_price_v2, format_token_amount, snapshot_pools, AmountStyle, BlockTimestamps, Config,
+    ConfigArgs, Finality, LogFetcher, Pool, PoolConfig, Price, ProtocolVersion, TokenRegistry,
+    UniswapV2Pair,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::fs;
-
-const RPC_URL: &str = "https://mainnet.gateway.tenderly.co";
-
-// DAI/WETH Uniswap V2 pair address
-const PAIR_ADDRESS: Address = address!("0xc4704f13d5e08b27b039d53873e813dd2fad99d9");
-
-sol! {
-    #[sol(rpc)]
-    contract UniswapV2Pair {
-        // Sync event - reserve changes after each swap
-        event Sync(uint112 reserve0, uint112 reserve1);
-
-        // Swap event - for volume calculation
-        event Swap(
-            address indexed sender,
-            uint256 amount0In,
-            uint256 amount1In,
-            uint256 amount0Out,
-            uint256 amount1Out,
-            address indexed to
-        );
-
-        function token0() external view returns (address);
-        function token1() external view returns (address);
-        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
-    }
-
-    #[sol(rpc)]
-    contract ERC20 {
-        function decimals() external view returns (uint8);
-        function symbol() external view returns (string);
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug)]
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+mod candles;
+mod live;
+mod output;
+mod questdb;
+mod server;
+#[cfg(test)]
+mod test_support;
+mod udf;
+
+use candles::{interval_label, parse_interval, roll_up, Candle};
+use output::OutputFormat;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct CandlestickData {
     timestamp: i64,
     open: String,
     high: String,
     low: String,
     close: String,
+    /// Traded amount in base token units
     volume: String,
+    /// Traded amount in quote token units
+    quote_volume: String,
+    /// Volume-weighted average price; null without trades
+    vwap: Option<String>,
+    /// Number of swaps
+    trades: u64,
+    /// Base amount traders bought from the pair
+    buy_volume: String,
+    /// Base amount traders sold to the pair
+    sell_volume: String,
+    first_block: Option<u64>,
+    last_block: Option<u64>,
+    first_tx: Option<B256>,
+    last_tx: Option<B256>,
 }
 
 #[derive(Debug, Clone)]
 struct PriceData {
     timestamp: DateTime<Utc>,
-    price: f64,
-    volume_usd: f64,
+    price: Price,
+    volume: SwapVolume,
+    /// Block and log index of the Sync event, so a reorged-out event can be
+    /// found again
+    block_number: u64,
+    log_index: u64,
+    tx_hash: Option<B256>,
+}
+
+/// Raw token amounts swapped through the pair.
+#[derive(Debug, Clone, Default)]
+struct SwapVolume {
+    base: U256,
+    quote: U256,
+    /// Base tokens traders took out of the pair
+    buy: U256,
+    /// Base tokens traders paid into the pair
+    sell: U256,
+    trades: u64,
+}
+
+impl SwapVolume {
+    fn from_swap(pool: &Pool, swap: &UniswapV2Pair::Swap) -> Self {
+        let (base_in, quote_in) = pool.orientation.base_quote(swap.amount0In, swap.amount1In);
+        let (base_out, quote_out) = pool
+            .orientation
+            .base_quote(swap.amount0Out, swap.amount1Out);
+        Self {
+            base: base_in + base_out,
+            quote: quote_in + quote_out,
+            buy: base_out,
+            sell: base_in,
+            trades: 1,
+        }
+    }
+
+    fn add(&mut self, other: &SwapVolume) {
+        self.base += other.base;
+        self.quote += other.quote;
+        self.buy += other.buy;
+        self.sell += other.sell;
+        self.trades += other.trades;
+    }
+}
+
+/// Where candles are built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Source {
+    /// Scan Sync and Swap events over RPC
+    Chain,
+    /// Aggregate swaps stored in QuestDB with SAMPLE BY
+    Questdb,
+}
+
+/// Build OHLC candles for a configured Uniswap V2 pair from its Sync and Swap
+/// events.
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Backend the candles are built from
+    #[arg(long, value_enum, default_value_t = Source::Chain)]
+    source: Source,
+
+    /// With --source questdb, only use swaps from this many minutes back;
+    /// defaults to all stored history
+    #[arg(long)]
+    lookback_minutes: Option<u64>,
+
+    /// Number of blocks before the latest one to scan
+    #[arg(long, default_value_t = 2000)]
+    blocks: u64,
+
+    /// Leave out blocks with fewer confirmations than this, so candles only
+    /// contain swaps that are final
+    #[arg(long, default_value_t = 0)]
+    confirmations: u64,
+
+    /// Candle interval such as 1m, 5m, 1h or 1d (bare numbers are minutes);
+    /// repeat for several resolutions, coarser ones are rolled up from finer
+    /// ones they are a multiple of
+    #[arg(
+        long = "interval",
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        default_values = ["1m"]
+    )]
+    intervals: Vec<u64>,
+
+    /// Blocks per eth_getLogs request; shrinks and regrows automatically
+    #[arg(long, default_value_t = 2000)]
+    log_chunk_blocks: u64,
+
+    /// eth_getLogs requests in flight at once
+    #[arg(long, default_value_t = 4)]
+    log_concurrency: usize,
+
+    /// Emit flat, zero-volume candles for intervals without trades so the
+    /// whole window is covered
+    #[arg(long)]
+    fill_gaps: bool,
+
+    /// After the backfill, keep following new Sync and Swap events and print
+    /// provisional and final candle updates to stdout as JSON lines; progress
+    /// goes to stderr
+    #[arg(long)]
+    follow: bool,
+
+    /// Instead of writing files, serve the candles of every selected pool
+    /// over HTTP on this address (with the chart page at /, and a
+    /// TradingView UDF datafeed at /udf) and keep them current from the chain
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<SocketAddr>,
+
+    /// File the candles are written to, candlestick_data.<format> by
+    /// default; with several pools or intervals each goes to its own file
+    /// with the pool name and interval appended, e.g.
+    /// candlestick_data_dai-weth_5m.json
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Output file format; defaults to the one matching the --output
+    /// extension, or json
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+}
+
+impl Cli {
+    fn output_format(&self) -> OutputFormat {
+        self.format
+            .or_else(|| self.output.as_deref().and_then(OutputFormat::from_path))
+            .unwrap_or(OutputFormat::Json)
+    }
+
+    /// Output file of one resolution; `pool` is the pool name when
+    /// candles of several pools are written.
+    fn output_path(&self, pool: Option<&str>, interval_minutes: u64) -> PathBuf {
+        let output = self.output.clone().unwrap_or_else(|| {
+            PathBuf::from(format!(
+                "candlestick_data.{}",
+                self.output_format().extension()
+            ))
+        });
+
+        let mut suffixes: Vec<String> = pool.map(str::to_string).into_iter().collect();
+        if self.intervals.len() > 1 {
+            suffixes.push(interval_label(interval_minutes));
+        }
+        if suffixes.is_empty() {
+            return output;
+        }
+
+        let stem = output
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut file_name = format!("{}_{}", stem, suffixes.join("_"));
+        if let Some(extension) = output.extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+
+        output.with_file_name(file_name)
+    }
+}
+
+/// The token and block timestamp caches, opened once per run and shared by
+/// every pool, so scans and followers never overwrite each other's files.
+#[derive(Clone)]
+struct Caches {
+    registry: Arc<TokenRegistry>,
+    /// One per chain, keyed by chain name
+    timestamps: BTreeMap<String, Arc<BlockTimestamps>>,
+}
+
+impl Caches {
+    fn open(config: &Config, pool_configs: &[&PoolConfig]) -> Result<Self> {
+        let mut timestamps = BTreeMap::new();
+        for pool_config in pool_configs {
+            if !timestamps.contains_key(&pool_config.chain) {
+                let chain = config.chain(pool_config);
+                timestamps.insert(
+                    pool_config.chain.clone(),
+                    Arc::new(config.block_timestamps(chain)?),
+                );
+            }
+        }
+
+        Ok(Self {
+            registry: Arc::new(config.token_registry()?),
+            timestamps,
+        })
+    }
+
+    /// Timestamps of the chain `pool_config` lives on.
+    fn timestamps(&self, pool_config: &PoolConfig) -> Arc<BlockTimestamps> {
+        self.timestamps[&pool_config.chain].clone()
+    }
+
+    /// Writes what the timestamp caches learned since their last save. The
+    /// long-running modes call this on Ctrl-C, where nothing is dropped.
+    fn save(&self) -> Result<()> {
+        for timestamps in self.timestamps.values() {
+            timestamps.save()?;
+        }
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let provider = ProviderBuilder::new().connect_http(RPC_URL.parse()?);
+    let mut cli = Cli::parse();
+    let config = cli.config.load()?;
 
-    // Get token info first
-    let pair_contract = UniswapV2Pair::new(PAIR_ADDRESS, &provider);
+    cli.intervals.sort_unstable();
+    cli.intervals.dedup();
+
+    if (cli.follow || cli.serve.is_some()) && cli.source != Source::Chain {
+        bail!("--follow and --serve need --source chain");
+    }
+
+    if let Some(addr) = cli.serve {
+        return server::serve(&cli, &config, addr).await;
+    }
+
+    let pool_configs = cli.config.select_pools(&config, ProtocolVersion::V2)?;
+    if cli.follow && pool_configs.len() > 1 {
+        bail!(
+            "--follow takes a single pool but {} are selected; pick one with --pool, or use --serve",
+            pool_configs.len()
+        );
+    }
 
-    let token0_addr = pair_contract.token0().call().await?;
-    let token1_addr = pair_contract.token1().call().await?;
+    let caches = Caches::open(&config, &pool_configs)?;
+    let several_pools = pool_configs.len() > 1;
+    for pool_config in pool_configs {
+        let backfill = write_candles(&cli, &config, &caches, pool_config, several_pools).await?;
+
+        if let Some(backfill) = backfill.filter(|_| cli.follow) {
+            let timestamps = caches.timestamps(pool_config);
+            let follow = live::follow(
+                &cli,
+                &config,
+                pool_config,
+                timestamps,
+                backfill,
+                live::print_update,
+            );
+
+            tokio::select! {
+                result = follow => result?,
+                _ = tokio::signal::ctrl_c() => eprintln!("Interrupted, saving caches"),
+            }
+            caches.save()?;
+        }
+    }
 
-    let token0_contract = ERC20::new(token0_addr, &provider);
-    let token1_contract = ERC20::new(token1_addr, &provider);
+    Ok(())
+}
 
-    let token0_symbol = token0_contract.symbol().call().await?;
-    let token1_symbol = token1_contract.symbol().call().await?;
+/// Builds the candles of one pool from the selected source and writes every
+/// resolution to its output file; returns the chain backfill for `--follow`.
+async fn write_candles(
+    cli: &Cli,
+    config: &Config,
+    caches: &Caches,
+    pool_config: &PoolConfig,
+    several_pools: bool,
+) -> Result<Option<ChainBackfill>> {
+    let (resolutions, backfill) = match cli.source {
+        Source::Chain => {
+            let (resolutions, backfill) =
+                candles_from_chain(cli, config, caches, pool_config).await?;
+            (resolutions, Some(backfill))
+        }
+        Source::Questdb => {
+            let questdb = config
+                .questdb
+                .as_ref()
+                .context("--source questdb needs a [questdb] section or --questdb-url")?;
+
+            eprintln!(
+                "🗄️  Reading {} swaps from QuestDB at {}",
+                pool_config.name, questdb.http_url
+            );
+
+            // Stored swaps carry no price from before the first of them, so
+            // filling starts there
+            let fill = GapFill {
+                seed: None,
+                end: Utc::now().timestamp(),
+                zero_volume: "0".to_string(),
+                zero_quote_volume: "0".to_string(),
+            };
+
+            // QuestDB aggregates every resolution from the stored swaps itself
+            let mut resolutions = Vec::new();
+            for &interval_minutes in &cli.intervals {
+                let candlesticks = questdb::fetch_candles(
+                    questdb,
+                    &pool_config.name,
+                    interval_minutes,
+                    cli.lookback_minutes,
+                    cli.config.invert,
+                )
+                .await?;
+
+                let candlesticks = if cli.fill_gaps {
+                    fill_gaps(candlesticks, interval_minutes, &fill)
+                } else {
+                    candlesticks
+                };
+                resolutions.push((interval_minutes, candlesticks));
+            }
+            (resolutions, None)
+        }
+    };
 
-    let token0_decimals = token0_contract.decimals().call().await?;
-    let token1_decimals = token1_contract.decimals().call().await?;
+    for (interval_minutes, candlesticks) in resolutions {
+        eprintln!(
+            "🕯️  Generated {} candlesticks ({} intervals)",
+            candlesticks.len(),
+            interval_label(interval_minutes)
+        );
 
-    println!("💱 Trading Pair: {} / {}", token0_symbol, token1_symbol);
+        // Save to file
+        let output = cli.output_path(
+            several_pools.then_some(pool_config.name.as_str()),
+            interval_minutes,
+        );
+        cli.output_format().write(&output, &candlesticks)?;
+        eprintln!("💾 Data saved to {}", output.display());
+
+        // Under --follow, stdout is reserved for the live JSON lines
+        if !cli.follow {
+            println!("\n📋 Candlestick Data (JSON):");
+            println!("{}", serde_json::to_string_pretty(&candlesticks)?);
+        }
+    }
+
+    Ok(backfill)
+}
+
+/// What a chain scan leaves behind for `--follow` to continue from.
+struct ChainBackfill {
+    pool: Pool,
+    /// Last block included in the scan.
+    to_block: u64,
+    price_data: Vec<PriceData>,
+}
+
+/// Scans the pair's recent Sync and Swap events once and aggregates them
+/// into every requested resolution locally.
+async fn candles_from_chain(
+    cli: &Cli,
+    config: &Config,
+    caches: &Caches,
+    pool_config: &PoolConfig,
+) -> Result<(Vec<(u64, Vec<CandlestickData>)>, ChainBackfill)> {
+    let chain = config.chain(pool_config);
+    let pair_address = pool_config.address;
+
+    let provider = ProviderBuilder::new().connect_http(chain.rpc_url.parse()?);
+
+    // Get token info first
+    let mut pool = Pool::fetch(
+        &provider,
+        &caches.registry,
+        chain.chain_id,
+        pair_address,
+        ProtocolVersion::V2,
+    )
+    .await?;
+    cli.config.orient(pool_config, &mut pool)?;
+
+    eprintln!(
+        "💱 Trading Pair: {} / {}",
+        pool.base().symbol,
+        pool.quote().symbol
+    );
 
     // Get recent blocks for historical data
-    let latest_block = provider.get_block_number().await?;
-    let from_block = latest_block.saturating_sub(2000); // Last ~2000 blocks (~8 hours)
+    let latest_block =
+        Finality::new(cli.confirmations).final_block(provider.get_block_number().await?);
+    let from_block = latest_block.saturating_sub(cli.blocks); // Default ~2000 blocks (~8 hours)
 
-    println!(
+    eprintln!(
         "🔍 Scanning blocks {} to {} for events",
         from_block, latest_block
     );
 
     // Fetch historical candlestick data
+    let timestamps = caches.timestamps(pool_config);
+
+    let log_fetcher = LogFetcher::new()
+        .chunk_size(cli.log_chunk_blocks)
+        .concurrency(cli.log_concurrency);
+
     let price_data = get_historical_price_data(
         &provider,
-        PAIR_ADDRESS,
+        &log_fetcher,
+        &timestamps,
+        &pool,
         from_block,
         latest_block,
-        token0_decimals,
-        token1_decimals,
     )
     .await?;
 
-    println!("📈 Found {} price data points", price_data.len());
-
-    let interval_minutes = 1;
-
-    // Create 5-minute candlesticks
-    let candlesticks = create_candlesticks(price_data, interval_minutes).await?;
-
-    println!(
-        "🕯️  Generated {} candlesticks ({} minute intervals)",
-        candlesticks.len(),
-        interval_minutes
-    );
-    println!(
-        "🕯️  Generated {} candlesticks ({} minute intervals)",
-        candlesticks.len(),
-        interval_minutes
-    );
-
-    // Output as JSON
-    let json_output = serde_json::to_string_pretty(&candlesticks)?;
-
-    // Save to file
-    let filename = "candlestick_data.json";
-    fs::write(filename, &json_output)?;
-    println!("💾 Data saved to {}", filename);
+    eprintln!("📈 Found {} price data points", price_data.len());
+
+    let built = build_resolutions(&price_data, &cli.intervals);
+
+    let to_data = |candles: Vec<Candle>| -> Vec<CandlestickData> {
+        candles
+            .iter()
+            .map(|candle| candle.to_data(pool.base().decimals, pool.quote().decimals))
+            .collect()
+    };
+
+    let resolutions = if cli.fill_gaps {
+        let fill = gap_fill(&provider, &timestamps, &pool, from_block, latest_block).await?;
+
+        // Gaps are filled per resolution so flat candles never leak into the
+        // roll-ups
+        built
+            .into_iter()
+            .map(|(interval_minutes, candles)| {
+                let candlesticks = fill_gaps(to_data(candles), interval_minutes, &fill);
+                (interval_minutes, candlesticks)
+            })
+            .collect()
+    } else {
+        built
+            .into_iter()
+            .map(|(interval_minutes, candles)| (interval_minutes, to_data(candles)))
+            .collect()
+    };
+
+    timestamps.save()?;
+
+    let backfill = ChainBackfill {
+        pool,
+        to_block: latest_block,
+        price_data,
+    };
+
+    Ok((resolutions, backfill))
+}
 
-    println!("\n📋 Candlestick Data (JSON):");
-    println!("{}", json_output);
+/// Window of the scan and the price going into it.
+async fn gap_fill(
+    provider: &impl Provider,
+    timestamps: &BlockTimestamps,
+    pool: &Pool,
+    from_block: u64,
+    to_block: u64,
+) -> Result<GapFill> {
+    let window = timestamps.resolve(provider, [from_block, to_block]).await?;
+
+    // The scan includes from_block's own events, so the price going into the
+    // window is the one left by the block before it
+    let seed_block = from_block.saturating_sub(1);
+    let seed = match snapshot_pools(
+        provider,
+        std::slice::from_ref(pool),
+        BlockId::number(seed_block),
+    )
+    .await
+    {
+        Ok(states) => states[0]
+            .as_ref()
+            .map(|state| format!("{:.32}", pool.orient_price(state.price(pool)))),
+        Err(err) => {
+            eprintln!(
+                "⚠️  Could not read reserves at block {} ({:#}); intervals before the first swap are left out",
+                seed_block, err
+            );
+            None
+        }
+    };
 
-    Ok(())
+    Ok(GapFill {
+        seed: seed.map(|price| (window[&from_block] as i64, price)),
+        end: window[&to_block] as i64,
+        zero_volume: zero_amount(pool.base().decimals),
+        zero_quote_volume: zero_amount(pool.quote().decimals),
+    })
 }
 
 async fn get_historical_price_data(
     provider: &impl Provider,
-    pair_address: Address,
+    log_fetcher: &LogFetcher,
+    timestamps: &BlockTimestamps,
+    pool: &Pool,
     from_block: u64,
     to_block: u64,
-    token0_decimals: u8,
-    token1_decimals: u8,
 ) -> Result<Vec<PriceData>> {
-    // Sync event signature: keccak256("Sync(uint112,uint112)")
-    let sync_signature = keccak256("Sync(uint112,uint112)");
+    // Sync carries the post-trade reserves, Swap the traded amounts
+    let filter = Filter::new().address(pool.address).event_signature(vec![
+        UniswapV2Pair::Sync::SIGNATURE_HASH,
+        UniswapV2Pair::Swap::SIGNATURE_HASH,
+    ]);
+
+    let mut logs = log_fetcher
+        .fetch(provider, &filter, from_block, to_block)
+        .await?;
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+    // Resolve each distinct block once, preferring timestamps the node
+    // already attached to the logs
+    for log in &logs {
+        if let (Some(block_number), Some(timestamp)) = (log.block_number, log.block_timestamp) {
+            timestamps.record(block_number, timestamp);
+        }
+    }
+    let block_timestamps = timestamps
+        .resolve(provider, logs.iter().filter_map(|log| log.block_number))
+        .await?;
 
-    let filter = Filter::new()
-        .address(pair_address)
-        .event_signature(sync_signature)
-        .from_block(from_block)
-        .to_block(to_block);
+    let mut price_data: Vec<PriceData> = Vec::new();
 
-    let logs = provider.get_logs(&filter).await?;
-    let mut price_data = Vec::new();
+    eprintln!("🔄 Processing {} Sync/Swap events...", logs.len());
 
-    println!("🔄 Processing {} Sync events...", logs.len());
+    // A V2 swap emits Sync then Swap from the same transaction; attach each
+    // Swap's amounts to that Sync, or hold them until it shows up
+    let mut last_sync: Option<(Option<B256>, usize)> = None;
+    let mut pending: Option<(Option<B256>, SwapVolume)> = None;
 
     for log in logs {
-        if let Ok(data) = parse_sync_event(&log, provider, token0_decimals, token1_decimals).await {
-            price_data.push(data);
+        let tx_hash = log.transaction_hash;
+
+        match log.topic0() {
+            Some(&UniswapV2Pair::Sync::SIGNATURE_HASH) => {
+                let Ok(mut data) = parse_sync_event(&log, &block_timestamps, pool) else {
+                    continue;
+                };
+
+                if let Some((pending_tx, volume)) = pending.take() {
+                    if pending_tx == tx_hash {
+                        data.volume.add(&volume);
+                    }
+                }
+
+                price_data.push(data);
+                last_sync = Some((tx_hash, price_data.len() - 1));
+            }
+            Some(&UniswapV2Pair::Swap::SIGNATURE_HASH) => {
+                let Ok(swap) = UniswapV2Pair::Swap::decode_log_data(log.data()) else {
+                    continue;
+                };
+                let volume = SwapVolume::from_swap(pool, &swap);
+
+                match last_sync {
+                    Some((sync_tx, index)) if sync_tx == tx_hash => {
+                        price_data[index].volume.add(&volume)
+                    }
+                    _ => match &mut pending {
+                        Some((pending_tx, pending_volume)) if *pending_tx == tx_hash => {
+                            pending_volume.add(&volume)
+                        }
+                        _ => pending = Some((tx_hash, volume)),
+                    },
+                }
+            }
+            _ => {}
         }
     }
 
@@ -167,83 +634,64 @@ async fn get_historical_price_data(
     Ok(price_data)
 }
 
-async fn parse_sync_event(
+fn parse_sync_event(
     log: &Log,
-    provider: &impl Provider,
-    token0_decimals: u8,
-    token1_decimals: u8,
+    block_timestamps: &HashMap<u64, u64>,
+    pool: &Pool,
 ) -> Result<PriceData> {
     // Parse event data: Sync(uint112 reserve0, uint112 reserve1)
     let data = &log.data().data;
 
     // Each uint112 takes 32 bytes in event data (padded)
-    let reserve0 = U256::from_be_slice(&data[0..32]).to::<u128>();
-    let reserve1 = U256::from_be_slice(&data[32..64]).to::<u128>();
+    let reserve0 = U256::from_be_slice(&data[0..32]);
+    let reserve1 = U256::from_be_slice(&data[32..64]);
 
     // Get block timestamp
     let block_number = log.block_number.unwrap_or_default();
-    let block = provider.get_block_by_number(block_number.into()).await?;
-    let timestamp = DateTime::from_timestamp(block.unwrap().header.timestamp as i64, 0)
-        .unwrap_or_else(|| Utc::now());
-
-    // Calculate price (token0 per token1)
-    let price = calculate_price_v2(reserve0, reserve1, token0_decimals, token1_decimals);
-
-    // Estimate volume (simplified - in real implementation you'd track swap events)
-    let volume_usd =
-        estimate_volume_from_reserves(reserve0, reserve1, token0_decimals, token1_decimals);
+    let block_timestamp = block_timestamps
+        .get(&block_number)
+        .with_context(|| format!("missing timestamp for block {}", block_number))?;
+    let timestamp = DateTime::from_timestamp(*block_timestamp as i64, 0).unwrap_or_else(Utc::now);
+
+    // Reserves give token1 per token0, reported as quote per base
+    let price = pool.orient_price(calculate_price_v2(
+        reserve0,
+        reserve1,
+        pool.token0.decimals,
+        pool.token1.decimals,
+    ));
 
     Ok(PriceData {
         timestamp,
         price,
-        volume_usd,
+        volume: SwapVolume::default(),
+        block_number,
+        log_index: log.log_index.unwrap_or_default(),
+        tx_hash: log.transaction_hash,
     })
 }
 
-fn calculate_price_v2(
-    reserve0: u128,
-    reserve1: u128,
-    token0_decimals: u8,
-    token1_decimals: u8,
-) -> f64 {
-    if reserve0 == 0 || reserve1 == 0 {
-        return 0.0;
+/// Candles of every interval in `intervals`, which must be sorted. The finest
+/// resolution comes from the events, coarser ones are rolled up from the
+/// finest one they are a multiple of.
+fn build_resolutions(price_data: &[PriceData], intervals: &[u64]) -> Vec<(u64, Vec<Candle>)> {
+    let mut built: Vec<(u64, Vec<Candle>)> = Vec::new();
+    for &interval_minutes in intervals {
+        let candles = match built
+            .iter()
+            .rev()
+            .find(|(finer, _)| interval_minutes.is_multiple_of(*finer))
+        {
+            Some((_, finer)) => roll_up(finer, interval_minutes),
+            None => create_candlesticks(price_data, interval_minutes),
+        };
+        built.push((interval_minutes, candles));
     }
-
-    let reserve0_f64 = reserve0 as f64;
-    let reserve1_f64 = reserve1 as f64;
-
-    // Price = reserve1 / reserve0 (token1 per token0)
-    // But we want token0 per token1, so we need reserve0 / reserve1
-    let price_ratio = reserve1_f64 / reserve0_f64;
-
-    // Adjust for decimal differences
-    let price_adjusted =
-        price_ratio * 10_f64.powi((token0_decimals as i32) - (token1_decimals as i32));
-
-    price_adjusted
-}
-
-fn estimate_volume_from_reserves(
-    reserve0: u128,
-    reserve1: u128,
-    token0_decimals: u8,
-    token1_decimals: u8,
-) -> f64 {
-    // Simplified volume estimation based on reserve size
-    // In real implementation, you'd sum up actual swap volumes
-    let reserve0_normalized = reserve0 as f64 / 10_f64.powi(token0_decimals as i32);
-    let reserve1_normalized = reserve1 as f64 / 10_f64.powi(token1_decimals as i32);
-
-    // Rough estimate: assume 0.1% of reserves traded per block
-    (reserve0_normalized + reserve1_normalized) * 0.001
+    built
 }
 
-async fn create_candlesticks(
-    price_data: Vec<PriceData>,
-    interval_minutes: u64,
-) -> Result<Vec<CandlestickData>> {
-    let mut intervals: BTreeMap<i64, Vec<PriceData>> = BTreeMap::new();
+fn create_candlesticks(price_data: &[PriceData], interval_minutes: u64) -> Vec<Candle> {
+    let mut intervals: BTreeMap<i64, Vec<&PriceData>> = BTreeMap::new();
 
     // Group price data by time intervals
     for data in price_data {
@@ -252,7 +700,7 @@ async fn create_candlesticks(
         intervals.entry(interval_start).or_default().push(data);
     }
 
-    let mut candlesticks = Vec::new();
+    let mut candles = Vec::new();
 
     // Create candlestick for each interval
     for (timestamp, mut interval_data) in intervals {
@@ -263,25 +711,251 @@ async fn create_candlesticks(
         // Sort by timestamp within interval
         interval_data.sort_by_key(|d| d.timestamp);
 
-        let prices: Vec<f64> = interval_data.iter().map(|d| d.price).collect();
-        let total_volume: f64 = interval_data.iter().map(|d| d.volume_usd).sum();
-
-        let open = *prices.first().unwrap_or(&0.0);
-        let close = *prices.last().unwrap_or(&0.0);
-        let high = prices.iter().fold(0.0f64, |a, &b| a.max(b));
-        let low = prices.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-
-        let candlestick = CandlestickData {
-            timestamp: timestamp * 1000, // Convert to milliseconds
-            open: format!("{:.32}", open),
-            high: format!("{:.32}", high),
-            low: format!("{:.32}", low),
-            close: format!("{:.32}", close),
-            volume: format!("{:.16}", total_volume),
-        };
+        let prices: Vec<&Price> = interval_data.iter().map(|d| &d.price).collect();
+        let mut total_volume = SwapVolume::default();
+        for data in &interval_data {
+            total_volume.add(&data.volume);
+        }
 
-        candlesticks.push(candlestick);
+        let open = prices[0];
+        let close = prices[prices.len() - 1];
+        let high = prices.iter().max().copied().unwrap_or(open);
+        let low = prices.iter().min().copied().unwrap_or(open);
+        let first = interval_data[0];
+        let last = interval_data[interval_data.len() - 1];
+
+        candles.push(Candle {
+            timestamp,
+            open: open.clone(),
+            high: high.clone(),
+            low: low.clone(),
+            close: close.clone(),
+            volume: total_volume,
+            first_block: first.block_number,
+            last_block: last.block_number,
+            first_tx: first.tx_hash,
+            last_tx: last.tx_hash,
+        });
     }
 
-    Ok(candlesticks)
+    candles
+}
+
+/// Window and placeholders for [`fill_gaps`].
+struct GapFill {
+    /// First second of the window and the price going into it; without one,
+    /// filling starts at the first real candle.
+    seed: Option<(i64, String)>,
+    /// Last second the candles should cover.
+    end: i64,
+    zero_volume: String,
+    zero_quote_volume: String,
+}
+
+/// Inserts a flat candle at the previous close, with zero volume, for every
+/// interval of the window that has no trades.
+fn fill_gaps(
+    candlesticks: Vec<CandlestickData>,
+    interval_minutes: u64,
+    fill: &GapFill,
+) -> Vec<CandlestickData> {
+    let interval_ms = interval_minutes as i64 * 60 * 1000;
+    let align = |seconds: i64| (seconds * 1000).div_euclid(interval_ms) * interval_ms;
+
+    let (mut next, mut previous_close) = match &fill.seed {
+        Some((start, price)) => (align(*start), Some(price.clone())),
+        None => (i64::MIN, None),
+    };
+    let mut filled = Vec::new();
+
+    for candle in candlesticks {
+        if let Some(close) = &previous_close {
+            while next < candle.timestamp {
+                filled.push(flat_candle(next, close, fill));
+                next += interval_ms;
+            }
+        }
+
+        next = candle.timestamp + interval_ms;
+        previous_close = Some(candle.close.clone());
+        filled.push(candle);
+    }
+
+    if let Some(close) = &previous_close {
+        while next <= align(fill.end) {
+            filled.push(flat_candle(next, close, fill));
+            next += interval_ms;
+        }
+    }
+
+    filled
+}
+
+fn flat_candle(timestamp: i64, price: &str, fill: &GapFill) -> CandlestickData {
+    CandlestickData {
+        timestamp,
+        open: price.to_string(),
+        high: price.to_string(),
+        low: price.to_string(),
+        close: price.to_string(),
+        volume: fill.zero_volume.clone(),
+        quote_volume: fill.zero_quote_volume.clone(),
+        vwap: None,
+        trades: 0,
+        buy_volume: fill.zero_volume.clone(),
+        sell_volume: fill.zero_volume.clone(),
+        first_block: None,
+        last_block: None,
+        first_tx: None,
+        last_tx: None,
+    }
+}
+
+fn zero_amount(decimals: u8) -> String {
+    format_token_amount(U256::ZERO, decimals, AmountStyle::Fixed(decimals as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::candle;
+
+    fn fill(seed: Option<(i64, &str)>, end: i64) -> GapFill {
+        GapFill {
+            seed: seed.map(|(start, price)| (start, price.to_string())),
+            end,
+            zero_volume: "0.00".to_string(),
+            zero_quote_volume: "0.0".to_string(),
+        }
+    }
+
+    /// `(timestamp in seconds, close, trades)` of every candle.
+    fn summary(candles: &[CandlestickData]) -> Vec<(i64, &str, u64)> {
+        candles
+            .iter()
+            .map(|candle| {
+                (
+                    candle.timestamp / 1000,
+                    candle.close.as_str(),
+                    candle.trades,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fills_from_the_seed_up_to_the_first_candle() {
+        let filled = fill_gaps(vec![candle(180, "2")], 1, &fill(Some((30, "1")), 180));
+
+        assert_eq!(
+            summary(&filled),
+            [(0, "1", 0), (60, "1", 0), (120, "1", 0), (180, "2", 1)]
+        );
+
+        let flat = &filled[0];
+        assert_eq!(flat.open, "1");
+        assert_eq!(flat.high, "1");
+        assert_eq!(flat.low, "1");
+        assert_eq!(flat.volume, "0.00");
+        assert_eq!(flat.quote_volume, "0.0");
+        assert_eq!(flat.buy_volume, "0.00");
+        assert_eq!(flat.vwap, None);
+        assert_eq!(flat.first_block, None);
+        assert_eq!(flat.last_block, None);
+    }
+
+    #[test]
+    fn fills_interior_gaps_at_the_previous_close() {
+        let filled = fill_gaps(
+            vec![candle(0, "1"), candle(180, "2"), candle(240, "3")],
+            1,
+            &fill(None, 240),
+        );
+
+        assert_eq!(
+            summary(&filled),
+            [
+                (0, "1", 1),
+                (60, "1", 0),
+                (120, "1", 0),
+                (180, "2", 1),
+                (240, "3", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn fills_after_the_last_candle_up_to_the_end() {
+        // The interval holding `end` is included
+        let filled = fill_gaps(vec![candle(0, "1")], 5, &fill(None, 700));
+
+        assert_eq!(
+            summary(&filled),
+            [(0, "1", 1), (300, "1", 0), (600, "1", 0)]
+        );
+    }
+
+    #[test]
+    fn starts_at_the_first_candle_without_a_seed() {
+        let filled = fill_gaps(vec![candle(120, "1")], 1, &fill(None, 120));
+        assert_eq!(summary(&filled), [(120, "1", 1)]);
+
+        // Nothing to carry forward without a seed or a candle
+        assert!(fill_gaps(Vec::new(), 1, &fill(None, 600)).is_empty());
+    }
+
+    /// A Sync at `seconds` pricing the base at `price`, with one swap of
+    /// `volume` base tokens in block `block_number`.
+    fn price_data(seconds: i64, price: u64, volume: u64, block_number: u64) -> PriceData {
+        PriceData {
+            timestamp: DateTime::from_timestamp(seconds, 0).unwrap(),
+            price: Price::from_ratio(U256::from(price), U256::from(1u8)),
+            volume: SwapVolume {
+                base: U256::from(volume),
+                quote: U256::from(volume * price),
+                buy: U256::from(volume),
+                sell: U256::ZERO,
+                trades: 1,
+            },
+            block_number,
+            log_index: 0,
+            tx_hash: None,
+        }
+    }
+
+    #[test]
+    fn coarser_resolutions_roll_up_unfilled_candles() {
+        // Trades in minutes 4 and 7, nothing in between
+        let price_data = [price_data(240, 10, 1, 100), price_data(420, 12, 2, 101)];
+        let built = build_resolutions(&price_data, &[1, 5]);
+
+        let (_, five_minutes) = &built[1];
+        assert_eq!(five_minutes.len(), 2);
+        // Built from real candles only, the second bucket opens at its own
+        // first trade rather than at a flat candle carrying the old close
+        let second = &five_minutes[1];
+        assert_eq!(second.timestamp, 300);
+        assert_eq!(
+            second.open,
+            Price::from_ratio(U256::from(12u8), U256::from(1u8))
+        );
+        assert_eq!(second.low, second.open);
+        assert_eq!(second.first_block, 101);
+        assert_eq!(second.volume.trades, 1);
+
+        // Filling each resolution afterwards still covers the quiet minutes
+        let fill = fill(None, 420);
+        let one_minute: Vec<CandlestickData> = built[0]
+            .1
+            .iter()
+            .map(|candle| candle.to_data(0, 0))
+            .collect();
+        assert_eq!(fill_gaps(one_minute, 1, &fill).len(), 4);
+    }
+
+    #[test]
+    fn seed_alone_fills_the_whole_window() {
+        let filled = fill_gaps(Vec::new(), 1, &fill(Some((0, "1")), 120));
+        assert_eq!(summary(&filled), [(0, "1", 0), (60, "1", 0), (120, "1", 0)]);
+    }
 }
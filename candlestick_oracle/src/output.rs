@@ -0,0 +1,337 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use alloy::primitives::B256;
+use anyhow::{Context, Result};
+use arrow::{
+    array::{
+        ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array,
+    },
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use clap::ValueEnum;
+use parquet::arrow::ArrowWriter;
+
+use crate::CandlestickData;
+
+/// File format candles are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Pretty-printed JSON array, prices as exact decimal strings
+    Json,
+    /// One JSON object per line, same fields as json
+    Jsonl,
+    /// Header row and numeric columns
+    Csv,
+    /// Typed columns: UTC millisecond timestamps and float64 values
+    Parquet,
+    /// Arrow IPC file with the same schema as parquet
+    Arrow,
+}
+
+impl OutputFormat {
+    /// Format matching a file extension such as `csv` or `parquet`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(Self::Json),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            "parquet" => Some(Self::Parquet),
+            "arrow" | "ipc" | "feather" => Some(Self::Arrow),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+            Self::Arrow => "arrow",
+        }
+    }
+
+    /// Writes `candles` to `path`, replacing the file.
+    pub fn write(self, path: &Path, candles: &[CandlestickData]) -> Result<()> {
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        match self {
+            Self::Json => serde_json::to_writer_pretty(&mut writer, candles)?,
+            Self::Jsonl => {
+                for candle in candles {
+                    serde_json::to_writer(&mut writer, candle)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            Self::Csv => {
+                let mut csv = arrow::csv::Writer::new(&mut writer);
+                csv.write(&record_batch(candles)?)?;
+            }
+            Self::Parquet => {
+                let batch = record_batch(candles)?;
+                let mut parquet = ArrowWriter::try_new(&mut writer, batch.schema(), None)?;
+                parquet.write(&batch)?;
+                parquet.close()?;
+            }
+            Self::Arrow => {
+                let batch = record_batch(candles)?;
+                let mut ipc =
+                    arrow::ipc::writer::FileWriter::try_new(&mut writer, &batch.schema())?;
+                ipc.write(&batch)?;
+                ipc.finish()?;
+            }
+        }
+
+        writer
+            .flush()
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// Candles as one columnar batch; prices and volumes become float64, which
+/// is what notebooks and warehouses work with, at the cost of the digits
+/// beyond double precision that the JSON formats keep.
+fn record_batch(candles: &[CandlestickData]) -> Result<RecordBatch> {
+    let schema = Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into())),
+            false,
+        ),
+        Field::new("open", DataType::Float64, false),
+        Field::new("high", DataType::Float64, false),
+        Field::new("low", DataType::Float64, false),
+        Field::new("close", DataType::Float64, false),
+        Field::new("volume", DataType::Float64, false),
+        Field::new("quote_volume", DataType::Float64, false),
+        Field::new("vwap", DataType::Float64, true),
+        Field::new("trades", DataType::UInt64, false),
+        Field::new("buy_volume", DataType::Float64, false),
+        Field::new("sell_volume", DataType::Float64, false),
+        Field::new("first_block", DataType::UInt64, true),
+        Field::new("last_block", DataType::UInt64, true),
+        Field::new("first_tx", DataType::Utf8, true),
+        Field::new("last_tx", DataType::Utf8, true),
+    ]);
+
+    let timestamps =
+        TimestampMillisecondArray::from_iter_values(candles.iter().map(|candle| candle.timestamp))
+            .with_timezone("+00:00");
+    let parse = |value: &str| {
+        value
+            .parse::<f64>()
+            .with_context(|| format!("invalid number `{}` in candle", value))
+    };
+    let column = |value: fn(&CandlestickData) -> &str| -> Result<ArrayRef> {
+        let values = candles
+            .iter()
+            .map(|candle| parse(value(candle)))
+            .collect::<Result<Vec<f64>>>()?;
+        Ok(Arc::new(Float64Array::from(values)))
+    };
+    let vwap = candles
+        .iter()
+        .map(|candle| candle.vwap.as_deref().map(parse).transpose())
+        .collect::<Result<Vec<Option<f64>>>>()?;
+    let blocks = |block: fn(&CandlestickData) -> Option<u64>| -> ArrayRef {
+        Arc::new(UInt64Array::from_iter(candles.iter().map(block)))
+    };
+    let hashes = |hash: fn(&CandlestickData) -> Option<B256>| -> ArrayRef {
+        Arc::new(StringArray::from_iter(
+            candles
+                .iter()
+                .map(|candle| hash(candle).map(|hash| hash.to_string())),
+        ))
+    };
+
+    let columns = vec![
+        Arc::new(timestamps) as ArrayRef,
+        column(|candle| &candle.open)?,
+        column(|candle| &candle.high)?,
+        column(|candle| &candle.low)?,
+        column(|candle| &candle.close)?,
+        column(|candle| &candle.volume)?,
+        column(|candle| &candle.quote_volume)?,
+        Arc::new(Float64Array::from(vwap)),
+        Arc::new(UInt64Array::from_iter_values(
+            candles.iter().map(|candle| candle.trades),
+        )),
+        column(|candle| &candle.buy_volume)?,
+        column(|candle| &candle.sell_volume)?,
+        blocks(|candle| candle.first_block),
+        blocks(|candle| candle.last_block),
+        hashes(|candle| candle.first_tx),
+        hashes(|candle| candle.last_tx),
+    ];
+
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use arrow::{
+        array::{Array, AsArray},
+        datatypes::{Float64Type, TimestampMillisecondType, UInt64Type},
+        ipc::reader::FileReader,
+    };
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support::candle;
+
+    /// A traded candle followed by a flat one without vwap or blocks.
+    fn candles() -> Vec<CandlestickData> {
+        let traded = CandlestickData {
+            first_block: Some(10),
+            last_block: Some(11),
+            first_tx: Some(B256::repeat_byte(0xaa)),
+            last_tx: Some(B256::repeat_byte(0xbb)),
+            ..candle(0, "2500.25")
+        };
+        let flat = CandlestickData {
+            vwap: None,
+            trades: 0,
+            first_block: None,
+            last_block: None,
+            ..candle(60, "2500.25")
+        };
+        vec![traded, flat]
+    }
+
+    /// Writes the sample candles as `format` into a fresh directory.
+    fn write(format: OutputFormat) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "candle_output_{}_{}",
+            std::process::id(),
+            format.extension()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("candles.{}", format.extension()));
+        format.write(&path, &candles()).unwrap();
+        path
+    }
+
+    fn assert_batch(batch: &RecordBatch) {
+        assert_eq!(batch.schema(), record_batch(&[]).unwrap().schema());
+        assert_eq!(batch.num_rows(), 2);
+
+        let timestamps = batch
+            .column_by_name("timestamp")
+            .unwrap()
+            .as_primitive::<TimestampMillisecondType>();
+        assert_eq!(timestamps.values(), &[0, 60_000]);
+
+        let close = batch
+            .column_by_name("close")
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        assert_eq!(close.value(0), 2500.25);
+
+        let vwap = batch.column_by_name("vwap").unwrap();
+        assert!(vwap.is_valid(0));
+        assert!(vwap.is_null(1));
+
+        let first_block = batch
+            .column_by_name("first_block")
+            .unwrap()
+            .as_primitive::<UInt64Type>();
+        assert_eq!(first_block.value(0), 10);
+        assert!(first_block.is_null(1));
+
+        let first_tx = batch.column_by_name("first_tx").unwrap().as_string::<i32>();
+        assert_eq!(first_tx.value(0), B256::repeat_byte(0xaa).to_string());
+        assert!(first_tx.is_null(1));
+    }
+
+    #[test]
+    fn parquet_keeps_schema_and_nulls() {
+        let path = write(OutputFormat::Parquet);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+        assert_batch(&batches[0]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn arrow_ipc_keeps_schema_and_nulls() {
+        let path = write(OutputFormat::Arrow);
+
+        let reader = FileReader::try_new(File::open(&path).unwrap(), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+        assert_batch(&batches[0]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn csv_has_a_header_and_utc_timestamps() {
+        let path = write(OutputFormat::Csv);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(
+            lines[0],
+            "timestamp,open,high,low,close,volume,quote_volume,vwap,trades,buy_volume,\
+             sell_volume,first_block,last_block,first_tx,last_tx"
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1970-01-01T00:00:00Z,2500.25,"));
+        assert!(lines[2].starts_with("1970-01-01T00:01:00Z,2500.25,"));
+        // Missing values are empty fields
+        assert!(lines[2].ends_with(",0,1.0,0.0,,,,"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn jsonl_writes_one_object_per_line() {
+        let path = write(OutputFormat::Jsonl);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(Value::is_object));
+        assert_eq!(lines[0]["close"], "2500.25");
+        assert_eq!(lines[0]["first_block"], 10);
+        assert_eq!(lines[1]["timestamp"], 60_000);
+        assert_eq!(lines[1]["vwap"], Value::Null);
+        assert_eq!(lines[1]["first_block"], Value::Null);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let path = write(OutputFormat::Json);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let read: Vec<CandlestickData> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].close, "2500.25");
+        assert_eq!(read[1].vwap, None);
+        assert_eq!(read[1].first_block, None);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+use oracle_core::QuestDbConfig;
+use serde::Deserialize;
+
+use crate::CandlestickData;
+
+/// Response of QuestDB's `/exec` endpoint; `error` is set instead of
+/// `dataset` when the query fails.
+#[derive(Debug, Deserialize)]
+struct ExecResponse {
+    #[serde(default)]
+    dataset: Vec<CandleRow>,
+    error: Option<String>,
+}
+
+/// One row of the `SAMPLE BY` query, in select order.
+#[derive(Debug, Deserialize)]
+struct CandleRow(
+    String,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    f64,
+    u64,
+    f64,
+    f64,
+    Option<u64>,
+    Option<u64>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Candles of `pool` aggregated by QuestDB from the swaps stored in its
+/// trades table, in the same shape as the on-chain backend produces.
+///
+/// The streamers store swaps in each pool's configured orientation whatever
+/// they print, so `invert` swaps base and quote of every candle exactly once.
+pub async fn fetch_candles(
+    config: &QuestDbConfig,
+    pool: &str,
+    interval_minutes: u64,
+    lookback_minutes: Option<u64>,
+    invert: bool,
+) -> Result<Vec<CandlestickData>> {
+    let query = candle_query(
+        &config.table,
+        pool,
+        interval_minutes,
+        lookback_minutes,
+        invert,
+    );
+
+    let url = format!("{}/exec", config.http_url.trim_end_matches('/'));
+    let response = reqwest::Client::new()
+        .get(&url)
+        .query(&[("query", query.as_str())])
+        .send()
+        .await
+        .with_context(|| format!("failed to query QuestDB at {}", url))?
+        .text()
+        .await
+        .with_context(|| format!("failed to read QuestDB response from {}", url))?;
+
+    parse_candles(&response)
+}
+
+/// The `SAMPLE BY` query aggregating the swaps of `pool` in `table` into
+/// candles of `interval_minutes`.
+fn candle_query(
+    table: &str,
+    pool: &str,
+    interval_minutes: u64,
+    lookback_minutes: Option<u64>,
+    invert: bool,
+) -> String {
+    let mut filter = format!("pool = '{}'", pool.replace('\'', "''"));
+    if let Some(minutes) = lookback_minutes {
+        filter.push_str(&format!(
+            " AND timestamp > dateadd('m', -{}, now())",
+            minutes
+        ));
+    }
+
+    // amount is in base units and price in quote per base, so their product
+    // is the quote amount of each swap; inverted, the highest price is the
+    // inverse of the lowest and a sell of the stored base buys the quote
+    let (prices, base, buy, sell) = if invert {
+        (
+            "1.0 / first(price) AS open, 1.0 / min(price) AS high, \
+             1.0 / max(price) AS low, 1.0 / last(price) AS close",
+            "amount * price",
+            "sell",
+            "buy",
+        )
+    } else {
+        (
+            "first(price) AS open, max(price) AS high, min(price) AS low, \
+             last(price) AS close",
+            "amount",
+            "buy",
+            "sell",
+        )
+    };
+    let quote = if invert { "amount" } else { "amount * price" };
+
+    // block_number is null for swaps stored before it was recorded
+    format!(
+        "SELECT timestamp, {prices}, sum({base}) AS volume, sum({quote}) AS quote_volume, \
+         count() AS trades, \
+         sum(CASE WHEN side = '{buy}' THEN {base} ELSE 0.0 END) AS buy_volume, \
+         sum(CASE WHEN side = '{sell}' THEN {base} ELSE 0.0 END) AS sell_volume, \
+         first(block_number) AS first_block, last(block_number) AS last_block, \
+         first(tx_hash) AS first_tx, last(tx_hash) AS last_tx \
+         FROM {} WHERE {} SAMPLE BY {}m ALIGN TO CALENDAR",
+        table, filter, interval_minutes
+    )
+}
+
+/// Candles from the body of an `/exec` response.
+fn parse_candles(body: &str) -> Result<Vec<CandlestickData>> {
+    let response: ExecResponse =
+        serde_json::from_str(body).context("failed to decode QuestDB response")?;
+
+    if let Some(error) = response.error {
+        bail!("QuestDB query failed: {}", error);
+    }
+
+    response
+        .dataset
+        .into_iter()
+        .map(|row| {
+            let CandleRow(
+                timestamp,
+                open,
+                high,
+                low,
+                close,
+                volume,
+                quote_volume,
+                trades,
+                buy_volume,
+                sell_volume,
+                first_block,
+                last_block,
+                first_tx,
+                last_tx,
+            ) = row;
+            let timestamp = DateTime::parse_from_rfc3339(&timestamp)
+                .with_context(|| format!("invalid timestamp `{}` from QuestDB", timestamp))?;
+
+            Ok(CandlestickData {
+                timestamp: timestamp.timestamp_millis(),
+                open: open.to_string(),
+                high: high.to_string(),
+                low: low.to_string(),
+                close: close.to_string(),
+                volume: volume.to_string(),
+                quote_volume: quote_volume.to_string(),
+                vwap: (volume > 0.0).then(|| (quote_volume / volume).to_string()),
+                trades,
+                buy_volume: buy_volume.to_string(),
+                sell_volume: sell_volume.to_string(),
+                first_block,
+                last_block,
+                first_tx: first_tx.and_then(|hash| hash.parse().ok()),
+                last_tx: last_tx.and_then(|hash| hash.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::B256;
+
+    use super::*;
+
+    #[test]
+    fn query_in_the_stored_orientation() {
+        assert_eq!(
+            candle_query("swaps", "dai-weth", 15, None, false),
+            "SELECT timestamp, first(price) AS open, max(price) AS high, min(price) AS low, \
+             last(price) AS close, sum(amount) AS volume, sum(amount * price) AS quote_volume, \
+             count() AS trades, \
+             sum(CASE WHEN side = 'buy' THEN amount ELSE 0.0 END) AS buy_volume, \
+             sum(CASE WHEN side = 'sell' THEN amount ELSE 0.0 END) AS sell_volume, \
+             first(block_number) AS first_block, last(block_number) AS last_block, \
+             first(tx_hash) AS first_tx, last(tx_hash) AS last_tx \
+             FROM swaps WHERE pool = 'dai-weth' SAMPLE BY 15m ALIGN TO CALENDAR"
+        );
+    }
+
+    #[test]
+    fn inverted_query_swaps_base_and_quote() {
+        assert_eq!(
+            candle_query("swaps", "dai-weth", 60, None, true),
+            "SELECT timestamp, 1.0 / first(price) AS open, 1.0 / min(price) AS high, \
+             1.0 / max(price) AS low, 1.0 / last(price) AS close, \
+             sum(amount * price) AS volume, sum(amount) AS quote_volume, count() AS trades, \
+             sum(CASE WHEN side = 'sell' THEN amount * price ELSE 0.0 END) AS buy_volume, \
+             sum(CASE WHEN side = 'buy' THEN amount * price ELSE 0.0 END) AS sell_volume, \
+             first(block_number) AS first_block, last(block_number) AS last_block, \
+             first(tx_hash) AS first_tx, last(tx_hash) AS last_tx \
+             FROM swaps WHERE pool = 'dai-weth' SAMPLE BY 60m ALIGN TO CALENDAR"
+        );
+    }
+
+    #[test]
+    fn query_escapes_the_pool_and_limits_the_lookback() {
+        let query = candle_query("swaps", "o'brien", 5, Some(120), false);
+        assert!(query.ends_with(
+            "FROM swaps WHERE pool = 'o''brien' \
+             AND timestamp > dateadd('m', -120, now()) SAMPLE BY 5m ALIGN TO CALENDAR"
+        ));
+    }
+
+    #[test]
+    fn parses_exec_rows() {
+        let tx = B256::repeat_byte(0xab);
+        let body = format!(
+            r#"{{
+                "query": "SELECT ...",
+                "columns": [],
+                "dataset": [
+                    ["2024-01-01T00:15:00.000000Z", 2500.0, 2510.5, 2490.0, 2505.0,
+                     2.0, 5010.0, 3, 1.5, 0.5, 100, 102, "{tx}", "{tx}"],
+                    ["2024-01-01T00:30:00.000000Z", 2505.0, 2505.0, 2505.0, 2505.0,
+                     0.0, 0.0, 1, 0.0, 0.0, null, null, null, "not a hash"]
+                ],
+                "count": 2
+            }}"#
+        );
+
+        let candles = parse_candles(&body).unwrap();
+        assert_eq!(candles.len(), 2);
+
+        let first = &candles[0];
+        assert_eq!(first.timestamp, 1_704_068_100_000);
+        assert_eq!(first.open, "2500");
+        assert_eq!(first.high, "2510.5");
+        assert_eq!(first.volume, "2");
+        assert_eq!(first.vwap.as_deref(), Some("2505"));
+        assert_eq!(first.trades, 3);
+        assert_eq!(first.buy_volume, "1.5");
+        assert_eq!(first.first_block, Some(100));
+        assert_eq!(first.last_block, Some(102));
+        assert_eq!(first.first_tx, Some(tx));
+
+        // Swaps stored without blocks or hashes, and no volume for a vwap
+        let second = &candles[1];
+        assert_eq!(second.vwap, None);
+        assert_eq!(second.first_block, None);
+        assert_eq!(second.first_tx, None);
+        assert_eq!(second.last_tx, None);
+    }
+
+    #[test]
+    fn missing_dataset_is_no_candles() {
+        let body = r#"{"query": "SELECT ...", "columns": [], "count": 0}"#;
+        assert!(parse_candles(body).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_query_errors() {
+        let body = r#"{"query": "SELECT ...", "error": "table does not exist [table=swaps]", "position": 42}"#;
+        let err = parse_candles(body).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "QuestDB query failed: table does not exist [table=swaps]"
+        );
+
+        assert!(parse_candles("<html>502 Bad Gateway</html>").is_err());
+        let bad_timestamp = r#"{"dataset": [["yesterday", 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0, 0.0, 0.0, null, null, null, null]]}"#;
+        assert!(parse_candles(bad_timestamp).is_err());
+    }
+}
@@ -0,0 +1,636 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+use alloy::primitives::{Address, B256};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use futures_util::future::try_join_all;
+use oracle_core::{format_token_amount, AmountStyle, Config, Pool, ProtocolVersion};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::TcpListener,
+    sync::broadcast::{self, error::RecvError},
+};
+
+use crate::{
+    candles::{interval_label, parse_interval},
+    candles_from_chain,
+    live::{self, FollowEvent},
+    udf, Caches, CandlestickData, Cli, PriceData,
+};
+
+const INDEX_HTML: &str = include_str!("../index.html");
+
+/// Live events buffered per WebSocket client before it starts missing some.
+const LIVE_EVENT_BUFFER: usize = 1024;
+
+/// Candles of every served pool and resolution, keyed by interval start.
+///
+/// Seeded from the backfill and kept current by the live builders; a
+/// provisional candle is replaced in place until its final version arrives.
+#[derive(Debug, Default)]
+pub struct CandleStore {
+    series: RwLock<HashMap<(String, u64), BTreeMap<i64, CandlestickData>>>,
+}
+
+impl CandleStore {
+    pub fn insert(&self, pool: &str, interval_minutes: u64, candles: Vec<CandlestickData>) {
+        let mut series = self.series.write().unwrap();
+        let candles_by_start = series
+            .entry((pool.to_string(), interval_minutes))
+            .or_default();
+        for candle in candles {
+            candles_by_start.insert(candle.timestamp, candle);
+        }
+    }
+
+    /// Candles starting within `from..=to` (unix milliseconds), or `None`
+    /// if the pool or interval is not served.
+    pub fn range(
+        &self,
+        pool: &str,
+        interval_minutes: u64,
+        from: i64,
+        to: i64,
+    ) -> Option<Vec<CandlestickData>> {
+        let series = self.series.read().unwrap();
+        let candles = series.get(&(pool.to_string(), interval_minutes))?;
+        if from > to {
+            return Some(Vec::new());
+        }
+        Some(
+            candles
+                .range(from..=to)
+                .map(|(_, candle)| candle.clone())
+                .collect(),
+        )
+    }
+
+    /// Start of the latest candle before `timestamp` (unix milliseconds).
+    pub fn last_before(&self, pool: &str, interval_minutes: u64, timestamp: i64) -> Option<i64> {
+        let series = self.series.read().unwrap();
+        let candles = series.get(&(pool.to_string(), interval_minutes))?;
+        candles
+            .range(..timestamp)
+            .next_back()
+            .map(|(&start, _)| start)
+    }
+}
+
+/// A served pool as listed by `GET /pools`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolInfo {
+    pub name: String,
+    pub chain: String,
+    pub address: Address,
+    pub base: String,
+    pub quote: String,
+    pub intervals: Vec<String>,
+}
+
+pub struct AppState {
+    pub store: CandleStore,
+    pub pools: Vec<PoolInfo>,
+    /// Served intervals in minutes, finest first
+    pub intervals: Vec<u64>,
+    live: broadcast::Sender<Arc<LiveEvent>>,
+}
+
+impl AppState {
+    pub fn new(pools: Vec<PoolInfo>, intervals: Vec<u64>) -> Self {
+        Self {
+            store: CandleStore::default(),
+            pools,
+            intervals,
+            live: broadcast::channel(LIVE_EVENT_BUFFER).0,
+        }
+    }
+
+    /// Parses an optional interval query value, defaulting to the finest
+    /// served one.
+    fn interval(&self, interval: Option<&str>) -> Result<u64, String> {
+        let interval_minutes = match interval.map(parse_interval) {
+            Some(minutes) => minutes?,
+            None => self.intervals[0],
+        };
+        if !self.intervals.contains(&interval_minutes) {
+            return Err(format!(
+                "interval {} is not served",
+                interval_label(interval_minutes)
+            ));
+        }
+        Ok(interval_minutes)
+    }
+}
+
+/// A followed event, serialized once for every client subscribed to it.
+#[derive(Debug)]
+struct LiveEvent {
+    pool: String,
+    /// `None` for trades, which go to every interval of the pool.
+    interval_minutes: Option<u64>,
+    message: String,
+}
+
+/// A swap as pushed to WebSocket clients.
+#[derive(Debug, Serialize)]
+struct TradeData {
+    /// Unix milliseconds
+    timestamp: i64,
+    price: String,
+    /// Traded amount in base token units
+    volume: String,
+    /// Traded amount in quote token units
+    quote_volume: String,
+    block_number: u64,
+    log_index: u64,
+    tx_hash: Option<B256>,
+}
+
+impl TradeData {
+    fn new(pool: &Pool, data: &PriceData) -> Self {
+        let (base_decimals, quote_decimals) = (pool.base().decimals, pool.quote().decimals);
+        Self {
+            timestamp: data.timestamp.timestamp_millis(),
+            price: format!("{:.32}", data.price),
+            volume: format_token_amount(
+                data.volume.base,
+                base_decimals,
+                AmountStyle::Fixed(base_decimals as usize),
+            ),
+            quote_volume: format_token_amount(
+                data.volume.quote,
+                quote_decimals,
+                AmountStyle::Fixed(quote_decimals as usize),
+            ),
+            block_number: data.block_number,
+            log_index: data.log_index,
+            tx_hash: data.tx_hash,
+        }
+    }
+}
+
+/// Messages a WebSocket client sends to `/ws`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Replaces the current subscription; `interval` defaults to the finest
+    /// served one.
+    Subscribe {
+        pool: String,
+        interval: Option<String>,
+    },
+}
+
+/// Messages `/ws` sends to a client.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscribed {
+        pool: &'a str,
+        interval: String,
+    },
+    Candle {
+        pool: &'a str,
+        interval: String,
+        status: &'static str,
+        candle: Box<CandlestickData>,
+    },
+    Trade {
+        pool: &'a str,
+        trade: TradeData,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Backfills every selected V2 pool, then serves their candles over HTTP
+/// while following the chain to keep them current.
+pub async fn serve(cli: &Cli, config: &Config, addr: SocketAddr) -> Result<()> {
+    let pool_configs = cli.config.select_pools(config, ProtocolVersion::V2)?;
+    let caches = Caches::open(config, &pool_configs)?;
+
+    let backfills = try_join_all(
+        pool_configs
+            .iter()
+            .map(|pool_config| candles_from_chain(cli, config, &caches, pool_config)),
+    )
+    .await?;
+
+    let labels: Vec<String> = cli
+        .intervals
+        .iter()
+        .map(|&interval_minutes| interval_label(interval_minutes))
+        .collect();
+    let mut state = AppState::new(Vec::new(), cli.intervals.clone());
+    let mut follows = Vec::new();
+    for (pool_config, (resolutions, backfill)) in pool_configs.iter().zip(backfills) {
+        for (interval_minutes, candlesticks) in resolutions {
+            state
+                .store
+                .insert(&pool_config.name, interval_minutes, candlesticks);
+        }
+        state.pools.push(PoolInfo {
+            name: pool_config.name.clone(),
+            chain: pool_config.chain.clone(),
+            address: pool_config.address,
+            base: backfill.pool.base().symbol.clone(),
+            quote: backfill.pool.quote().symbol.clone(),
+            intervals: labels.clone(),
+        });
+        follows.push((*pool_config, backfill));
+    }
+    let state = Arc::new(state);
+    let app = router(state.clone());
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to listen on {}", addr))?;
+    println!("🌐 Serving candles on http://{}", addr);
+
+    let follows = try_join_all(follows.into_iter().map(|(pool_config, backfill)| {
+        let state = state.clone();
+        let timestamps = caches.timestamps(pool_config);
+        live::follow(
+            cli,
+            config,
+            pool_config,
+            timestamps,
+            backfill,
+            move |pool, event| publish(&state, &pool_config.name, pool, event),
+        )
+    }));
+
+    let served = async {
+        tokio::try_join!(
+            async {
+                axum::serve(listener, app)
+                    .await
+                    .context("HTTP server failed")
+            },
+            follows,
+        )
+    };
+
+    tokio::select! {
+        result = served => {
+            result?;
+        }
+        _ = tokio::signal::ctrl_c() => eprintln!("Interrupted, saving caches"),
+    }
+    caches.save()
+}
+
+fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/index.html", get(index))
+        .route("/pools", get(pools))
+        .route("/candles", get(candles))
+        .route("/ws", get(ws))
+        .nest("/udf", udf::router())
+        .with_state(state)
+}
+
+/// Stores a followed candle update and pushes it, like new trades, to the
+/// subscribed WebSocket clients.
+fn publish(state: &AppState, name: &str, pool: &Pool, event: FollowEvent) -> Result<()> {
+    let (interval_minutes, message) = match event {
+        FollowEvent::Candle(interval_minutes, update) => {
+            let candle = update
+                .candle()
+                .to_data(pool.base().decimals, pool.quote().decimals);
+            state
+                .store
+                .insert(name, interval_minutes, vec![candle.clone()]);
+
+            let message = ServerMessage::Candle {
+                pool: name,
+                interval: interval_label(interval_minutes),
+                status: update.status(),
+                candle: Box::new(candle),
+            };
+            (Some(interval_minutes), message)
+        }
+        FollowEvent::Trade(data) => {
+            let trade = TradeData::new(pool, &data);
+            (None, ServerMessage::Trade { pool: name, trade })
+        }
+    };
+
+    // Sending only fails while no client is connected
+    let _ = state.live.send(Arc::new(LiveEvent {
+        pool: name.to_string(),
+        interval_minutes,
+        message: serde_json::to_string(&message)?,
+    }));
+    Ok(())
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+async fn pools(State(state): State<Arc<AppState>>) -> Json<Vec<PoolInfo>> {
+    Json(state.pools.clone())
+}
+
+#[derive(Debug, Deserialize)]
+struct CandlesQuery {
+    pool: String,
+    /// Defaults to the finest served interval
+    interval: Option<String>,
+    /// Unix milliseconds, inclusive, like the candle timestamps
+    from: Option<i64>,
+    /// Unix milliseconds, inclusive
+    to: Option<i64>,
+}
+
+async fn candles(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CandlesQuery>,
+) -> Response {
+    let interval_minutes = match state.interval(query.interval.as_deref()) {
+        Ok(interval_minutes) => interval_minutes,
+        Err(error) => return (StatusCode::BAD_REQUEST, error).into_response(),
+    };
+
+    let from = query.from.unwrap_or(i64::MIN);
+    let to = query.to.unwrap_or(i64::MAX);
+
+    match state.store.range(&query.pool, interval_minutes, from, to) {
+        Some(candles) => Json(candles).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("pool `{}` is not served", query.pool),
+        )
+            .into_response(),
+    }
+}
+
+async fn ws(State(state): State<Arc<AppState>>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| push_updates(state, socket))
+}
+
+/// Forwards the live events of the client's current subscription until the
+/// client goes away. Nothing is sent before the first subscribe message.
+async fn push_updates(state: Arc<AppState>, mut socket: WebSocket) {
+    let mut events = state.live.subscribe();
+    let mut subscription: Option<(String, u64)> = None;
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    match subscribe(&state, &text, &mut subscription) {
+                        Ok(reply) => reply,
+                        Err(_) => return,
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let Some((pool, interval_minutes)) = &subscription else {
+                        continue;
+                    };
+                    let subscribed = event.pool == *pool
+                        && event.interval_minutes.is_none_or(|interval| interval == *interval_minutes);
+                    if !subscribed {
+                        continue;
+                    }
+                    event.message.clone()
+                }
+                // The client fell behind; it keeps receiving from here and
+                // can reload /candles to catch up
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+        };
+
+        if socket.send(Message::Text(reply.into())).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Handles one client message, returning the reply to send.
+fn subscribe(
+    state: &AppState,
+    text: &str,
+    subscription: &mut Option<(String, u64)>,
+) -> Result<String> {
+    let reply = match parse_subscription(state, text) {
+        Ok(subscribed) => {
+            let (pool, interval_minutes) = subscription.insert(subscribed);
+            ServerMessage::Subscribed {
+                pool,
+                interval: interval_label(*interval_minutes),
+            }
+        }
+        Err(message) => ServerMessage::Error { message },
+    };
+    Ok(serde_json::to_string(&reply)?)
+}
+
+fn parse_subscription(state: &AppState, text: &str) -> Result<(String, u64), String> {
+    let ClientMessage::Subscribe { pool, interval } =
+        serde_json::from_str(text).map_err(|error| format!("invalid message: {}", error))?;
+
+    if !state.pools.iter().any(|served| served.name == pool) {
+        return Err(format!("pool `{}` is not served", pool));
+    }
+    let interval_minutes = state.interval(interval.as_deref())?;
+    Ok((pool, interval_minutes))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::U256;
+    use axum::body::to_bytes;
+    use chrono::DateTime;
+    use futures_util::{SinkExt, StreamExt};
+    use oracle_core::{Orientation, Price};
+    use serde_json::Value;
+    use tokio_tungstenite::{connect_async, tungstenite};
+
+    use super::*;
+    use crate::{
+        test_support::{candle, pool},
+        SwapVolume,
+    };
+
+    /// Pool `dai-weth` served at 1m and 5m, with 1m candles at 0, 60, 120
+    /// and 300 seconds.
+    fn state() -> Arc<AppState> {
+        let pools = vec![PoolInfo {
+            name: "dai-weth".to_string(),
+            chain: "mainnet".to_string(),
+            address: pool(Orientation::Token1Base).address,
+            base: "WETH".to_string(),
+            quote: "DAI".to_string(),
+            intervals: vec!["1m".to_string(), "5m".to_string()],
+        }];
+        let state = AppState::new(pools, vec![1, 5]);
+        state.store.insert(
+            "dai-weth",
+            1,
+            vec![
+                candle(0, "1"),
+                candle(60, "2"),
+                candle(120, "3"),
+                candle(300, "4"),
+            ],
+        );
+        Arc::new(state)
+    }
+
+    async fn get_candles(query: CandlesQuery) -> (StatusCode, Vec<u8>) {
+        let response = candles(State(state()), Query(query)).await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    fn query(
+        pool: &str,
+        interval: Option<&str>,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> CandlesQuery {
+        CandlesQuery {
+            pool: pool.to_string(),
+            interval: interval.map(str::to_string),
+            from,
+            to,
+        }
+    }
+
+    fn timestamps(body: &[u8]) -> Vec<i64> {
+        let candles: Vec<Value> = serde_json::from_slice(body).unwrap();
+        candles
+            .iter()
+            .map(|candle| candle["timestamp"].as_i64().unwrap())
+            .collect()
+    }
+
+    async fn next_json<S>(socket: &mut S) -> Value
+    where
+        S: StreamExt<Item = tungstenite::Result<tungstenite::Message>> + Unpin,
+    {
+        let message = socket.next().await.unwrap().unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn subscribed_client_receives_trades() {
+        let pools = vec![PoolInfo {
+            name: "dai-weth".to_string(),
+            chain: "mainnet".to_string(),
+            address: pool(Orientation::Token1Base).address,
+            base: "WETH".to_string(),
+            quote: "DAI".to_string(),
+            intervals: vec!["1m".to_string()],
+        }];
+        let state = Arc::new(AppState::new(pools, vec![1]));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        socket
+            .send(tungstenite::Message::text(
+                r#"{"type":"subscribe","pool":"dai-weth"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut socket).await["type"], "subscribed");
+
+        let data = PriceData {
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            price: Price::from_amounts(U256::from(1u64), 0, U256::from(2_000u64), 0),
+            volume: SwapVolume {
+                base: U256::from(10u64).pow(U256::from(18u64)),
+                quote: U256::from(2_000u64) * U256::from(10u64).pow(U256::from(18u64)),
+                buy: U256::from(10u64).pow(U256::from(18u64)),
+                sell: U256::ZERO,
+                trades: 1,
+            },
+            block_number: 100,
+            log_index: 3,
+            tx_hash: Some(B256::repeat_byte(0xab)),
+        };
+        publish(
+            &state,
+            "dai-weth",
+            &pool(Orientation::Token1Base),
+            FollowEvent::Trade(data),
+        )
+        .unwrap();
+
+        let message = next_json(&mut socket).await;
+        assert_eq!(message["type"], "trade");
+        assert_eq!(message["pool"], "dai-weth");
+        assert_eq!(message["trade"]["block_number"], 100);
+        assert_eq!(message["trade"]["log_index"], 3);
+        assert!(message["trade"]["volume"]
+            .as_str()
+            .unwrap()
+            .starts_with("1.000"));
+        assert!(message["trade"]["price"]
+            .as_str()
+            .unwrap()
+            .starts_with("2000"));
+    }
+
+    #[tokio::test]
+    async fn candles_range_is_in_milliseconds() {
+        let (status, body) = get_candles(query("dai-weth", None, None, None)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(timestamps(&body), [0, 60_000, 120_000, 300_000]);
+
+        // Both bounds are inclusive and match the payload timestamps
+        let (_, body) =
+            get_candles(query("dai-weth", Some("1m"), Some(60_000), Some(120_000))).await;
+        assert_eq!(timestamps(&body), [60_000, 120_000]);
+
+        let (_, body) = get_candles(query("dai-weth", None, Some(60_001), None)).await;
+        assert_eq!(timestamps(&body), [120_000, 300_000]);
+
+        let (_, body) = get_candles(query("dai-weth", None, Some(120_000), Some(60_000))).await;
+        assert!(timestamps(&body).is_empty());
+    }
+
+    #[tokio::test]
+    async fn candles_of_unknown_pool_are_not_found() {
+        let (status, body) = get_candles(query("usdc-weth", None, None, None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, b"pool `usdc-weth` is not served");
+    }
+
+    #[tokio::test]
+    async fn candles_of_unserved_interval_are_rejected() {
+        let (status, body) = get_candles(query("dai-weth", Some("15m"), None, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, b"interval 15m is not served");
+
+        let (status, _) = get_candles(query("dai-weth", Some("soon"), None, None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,38 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use alloy::primitives::Address;
+use oracle_core::{Orientation, Pool, ProtocolVersion, Token};
+
+use crate::CandlestickData;
+
+/// The DAI/WETH pair, DAI being token0, reported in `orientation`.
+pub fn pool(orientation: Orientation) -> Pool {
+    Pool {
+        address: Address::repeat_byte(0x01),
+        version: ProtocolVersion::V2,
+        token0: Token::new(Address::repeat_byte(0x0a), "DAI", 18),
+        token1: Token::new(Address::repeat_byte(0x0b), "WETH", 18),
+        orientation,
+    }
+}
+
+/// A one-trade candle starting at `timestamp` seconds, flat at `close`.
+pub fn candle(timestamp: i64, close: &str) -> CandlestickData {
+    CandlestickData {
+        timestamp: timestamp * 1000,
+        open: close.to_string(),
+        high: close.to_string(),
+        low: close.to_string(),
+        close: close.to_string(),
+        volume: "1".to_string(),
+        quote_volume: close.to_string(),
+        vwap: Some(close.to_string()),
+        trades: 1,
+        buy_volume: "1".to_string(),
+        sell_volume: "0".to_string(),
+        first_block: Some(1),
+        last_block: Some(1),
+        first_tx: None,
+        last_tx: None,
+    }
+}
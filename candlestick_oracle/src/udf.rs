@@ -0,0 +1,386 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{AppState, PoolInfo};
+
+/// Exchange name shown next to every symbol.
+const EXCHANGE: &str = "Uniswap V2";
+
+/// Significant digits the price scale of a symbol aims for.
+const PRICE_DIGITS: i32 = 8;
+
+/// Endpoints of TradingView's UDF datafeed protocol, with every served pool
+/// as a symbol named after it.
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/config", get(config))
+        .route("/symbols", get(symbols))
+        .route("/search", get(search))
+        .route("/history", get(history))
+        .route("/time", get(time))
+}
+
+/// UDF resolution of an interval: minutes, or days as `1D`.
+fn resolution(interval_minutes: u64) -> String {
+    if interval_minutes.is_multiple_of(24 * 60) {
+        format!("{}D", interval_minutes / (24 * 60))
+    } else {
+        interval_minutes.to_string()
+    }
+}
+
+/// Minutes of a UDF resolution such as `5`, `60`, `D` or `1D`.
+fn parse_resolution(resolution: &str) -> Option<u64> {
+    match resolution.strip_suffix('D') {
+        Some("") => Some(24 * 60),
+        Some(days) => days.parse::<u64>().ok()?.checked_mul(24 * 60),
+        None => resolution.parse().ok(),
+    }
+}
+
+/// Served pool named by a UDF symbol, which may carry an `EXCHANGE:` prefix.
+fn find_pool<'a>(state: &'a AppState, symbol: &str) -> Option<&'a PoolInfo> {
+    let name = symbol.rsplit(':').next().unwrap_or(symbol);
+    state.pools.iter().find(|pool| pool.name == name)
+}
+
+#[derive(Debug, Serialize)]
+struct UdfError {
+    s: &'static str,
+    errmsg: String,
+}
+
+fn error(message: impl Into<String>) -> Response {
+    Json(UdfError {
+        s: "error",
+        errmsg: message.into(),
+    })
+    .into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct DatafeedConfig {
+    supported_resolutions: Vec<String>,
+    supports_search: bool,
+    supports_group_request: bool,
+    supports_marks: bool,
+    supports_timescale_marks: bool,
+    supports_time: bool,
+}
+
+async fn config(State(state): State<Arc<AppState>>) -> Json<DatafeedConfig> {
+    Json(DatafeedConfig {
+        supported_resolutions: state.intervals.iter().copied().map(resolution).collect(),
+        supports_search: true,
+        supports_group_request: false,
+        supports_marks: false,
+        supports_timescale_marks: false,
+        supports_time: true,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct SymbolInfo {
+    name: String,
+    ticker: String,
+    description: String,
+    #[serde(rename = "type")]
+    symbol_type: &'static str,
+    session: &'static str,
+    timezone: &'static str,
+    exchange: &'static str,
+    listed_exchange: &'static str,
+    minmov: u32,
+    pricescale: u64,
+    has_intraday: bool,
+    intraday_multipliers: Vec<String>,
+    has_daily: bool,
+    daily_multipliers: Vec<String>,
+    has_weekly_and_monthly: bool,
+    supported_resolutions: Vec<String>,
+    volume_precision: u32,
+    data_status: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolQuery {
+    symbol: String,
+}
+
+async fn symbols(State(state): State<Arc<AppState>>, Query(query): Query<SymbolQuery>) -> Response {
+    let Some(pool) = find_pool(&state, &query.symbol) else {
+        return error("unknown_symbol");
+    };
+
+    let (daily, intraday): (Vec<u64>, Vec<u64>) = state
+        .intervals
+        .iter()
+        .partition(|minutes| minutes.is_multiple_of(24 * 60));
+
+    Json(SymbolInfo {
+        name: pool.name.clone(),
+        ticker: pool.name.clone(),
+        description: description(pool),
+        symbol_type: "crypto",
+        session: "24x7",
+        timezone: "Etc/UTC",
+        exchange: EXCHANGE,
+        listed_exchange: EXCHANGE,
+        minmov: 1,
+        pricescale: price_scale(&state, pool),
+        has_intraday: !intraday.is_empty(),
+        intraday_multipliers: intraday.iter().map(u64::to_string).collect(),
+        has_daily: !daily.is_empty(),
+        daily_multipliers: daily
+            .iter()
+            .map(|minutes| (minutes / (24 * 60)).to_string())
+            .collect(),
+        has_weekly_and_monthly: false,
+        supported_resolutions: state.intervals.iter().copied().map(resolution).collect(),
+        volume_precision: 8,
+        data_status: "streaming",
+    })
+    .into_response()
+}
+
+fn description(pool: &PoolInfo) -> String {
+    format!("{}/{} on {}", pool.base, pool.quote, pool.chain)
+}
+
+/// Power of ten that shows the latest price with [`PRICE_DIGITS`]
+/// significant digits; prices of pairs with very different decimals can be
+/// far below one.
+fn price_scale(state: &AppState, pool: &PoolInfo) -> u64 {
+    let latest = state
+        .store
+        .range(&pool.name, state.intervals[0], i64::MIN, i64::MAX)
+        .and_then(|candles| candles.last()?.close.parse::<f64>().ok())
+        .filter(|price| *price > 0.0);
+
+    let decimals = latest.map_or(PRICE_DIGITS, |price| {
+        PRICE_DIGITS - 1 - price.log10().floor() as i32
+    });
+    10u64.pow(decimals.clamp(0, 18) as u32)
+}
+
+#[derive(Debug, Serialize)]
+struct SearchResult {
+    symbol: String,
+    full_name: String,
+    description: String,
+    exchange: &'static str,
+    ticker: String,
+    #[serde(rename = "type")]
+    symbol_type: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    query: String,
+    limit: Option<usize>,
+}
+
+async fn search(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SearchQuery>,
+) -> Json<Vec<SearchResult>> {
+    let needle = query.query.to_lowercase();
+    let results = state
+        .pools
+        .iter()
+        .filter(|pool| {
+            pool.name.to_lowercase().contains(&needle)
+                || description(pool).to_lowercase().contains(&needle)
+        })
+        .take(query.limit.unwrap_or(usize::MAX))
+        .map(|pool| SearchResult {
+            symbol: pool.name.clone(),
+            full_name: format!("{}:{}", EXCHANGE, pool.name),
+            description: description(pool),
+            exchange: EXCHANGE,
+            ticker: pool.name.clone(),
+            symbol_type: "crypto",
+        })
+        .collect();
+
+    Json(results)
+}
+
+/// Bars in UDF's column layout; `nextTime` points at the latest earlier bar
+/// when the requested range is empty.
+#[derive(Debug, Serialize)]
+#[serde(tag = "s", rename_all = "snake_case")]
+enum History {
+    Ok {
+        t: Vec<i64>,
+        o: Vec<f64>,
+        h: Vec<f64>,
+        l: Vec<f64>,
+        c: Vec<f64>,
+        v: Vec<f64>,
+    },
+    NoData {
+        #[serde(rename = "nextTime", skip_serializing_if = "Option::is_none")]
+        next_time: Option<i64>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    symbol: String,
+    resolution: String,
+    /// Unix seconds, inclusive
+    from: i64,
+    /// Unix seconds, exclusive
+    to: i64,
+    /// Bars wanted up to `to`; takes precedence over `from`
+    countback: Option<usize>,
+}
+
+async fn history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let Some(pool) = find_pool(&state, &query.symbol) else {
+        return error("unknown_symbol");
+    };
+    let Some(interval_minutes) = parse_resolution(&query.resolution)
+        .filter(|interval_minutes| state.intervals.contains(interval_minutes))
+    else {
+        return error(format!("unsupported resolution {}", query.resolution));
+    };
+
+    // Bars start on whole seconds, so the last millisecond before `to` ends
+    // the range
+    let to = query.to.saturating_mul(1000);
+    let from = match query.countback {
+        Some(_) => i64::MIN,
+        None => query.from.saturating_mul(1000),
+    };
+    let Some(mut candles) =
+        state
+            .store
+            .range(&pool.name, interval_minutes, from, to.saturating_sub(1))
+    else {
+        return error("unknown_symbol");
+    };
+    if let Some(countback) = query.countback {
+        candles.drain(..candles.len().saturating_sub(countback));
+    }
+
+    if candles.is_empty() {
+        let before = match query.countback {
+            Some(_) => to,
+            None => from.min(to),
+        };
+        let next_time = state
+            .store
+            .last_before(&pool.name, interval_minutes, before)
+            .map(|start| start / 1000);
+        return Json(History::NoData { next_time }).into_response();
+    }
+
+    let number = |value: &str| value.parse::<f64>().unwrap_or(f64::NAN);
+    Json(History::Ok {
+        t: candles
+            .iter()
+            .map(|candle| candle.timestamp / 1000)
+            .collect(),
+        o: candles.iter().map(|candle| number(&candle.open)).collect(),
+        h: candles.iter().map(|candle| number(&candle.high)).collect(),
+        l: candles.iter().map(|candle| number(&candle.low)).collect(),
+        c: candles.iter().map(|candle| number(&candle.close)).collect(),
+        v: candles
+            .iter()
+            .map(|candle| number(&candle.volume))
+            .collect(),
+    })
+    .into_response()
+}
+
+async fn time() -> String {
+    Utc::now().timestamp().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Address;
+    use axum::body::to_bytes;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support::candle;
+
+    /// Pool `dai-weth` with 1m bars at 0, 60, 120 and 300 seconds.
+    fn state() -> Arc<AppState> {
+        let pools = vec![PoolInfo {
+            name: "dai-weth".to_string(),
+            chain: "mainnet".to_string(),
+            address: Address::ZERO,
+            base: "WETH".to_string(),
+            quote: "DAI".to_string(),
+            intervals: vec!["1m".to_string()],
+        }];
+        let state = AppState::new(pools, vec![1]);
+        state.store.insert(
+            "dai-weth",
+            1,
+            vec![
+                candle(0, "1"),
+                candle(60, "2"),
+                candle(120, "3"),
+                candle(300, "4"),
+            ],
+        );
+        Arc::new(state)
+    }
+
+    async fn get_history(from: i64, to: i64, countback: Option<usize>) -> Value {
+        let query = HistoryQuery {
+            symbol: "Uniswap V2:dai-weth".to_string(),
+            resolution: "1".to_string(),
+            from,
+            to,
+            countback,
+        };
+        let response = history(State(state()), Query(query)).await;
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn history_excludes_to() {
+        let first = get_history(0, 120, None).await;
+        assert_eq!(first["s"], "ok");
+        assert_eq!(first["t"], serde_json::json!([0, 60]));
+
+        let next = get_history(120, 360, None).await;
+        assert_eq!(next["t"], serde_json::json!([120, 300]));
+    }
+
+    #[tokio::test]
+    async fn history_counts_back_from_to() {
+        let bars = get_history(0, 300, Some(2)).await;
+        assert_eq!(bars["t"], serde_json::json!([60, 120]));
+    }
+
+    #[tokio::test]
+    async fn empty_history_points_at_the_previous_bar() {
+        let gap = get_history(180, 300, None).await;
+        assert_eq!(gap["s"], "no_data");
+        assert_eq!(gap["nextTime"], 120);
+
+        let before_first = get_history(-600, 0, Some(5)).await;
+        assert_eq!(before_first["s"], "no_data");
+        assert!(before_first.get("nextTime").is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use alloy::primitives::U256;
+
+/// How [`format_token_amount`] lays out a scaled token amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountStyle {
+    /// At least this many significant digits; the integer part is never rounded.
+    Significant(usize),
+    /// Exactly this many digits after the decimal point.
+    Fixed(usize),
+    /// One fractional digit with a K/M/B/T suffix, e.g. `1.2K` or `3.4M`;
+    /// amounts below one keep enough places to show two significant digits.
+    Compact,
+}
+
+const COMPACT_SUFFIXES: [&str; 5] = ["", "K", "M", "B", "T"];
+
+/// Lossy conversion of a raw token amount to whole tokens, for sinks that
+/// store doubles.
+pub fn token_amount_to_f64(amount: U256, decimals: u8) -> f64 {
+    format_token_amount(amount, decimals, AmountStyle::Fixed(decimals as usize))
+        .parse()
+        .unwrap_or(f64::NAN)
+}
+
+/// Formats a raw token amount with `decimals` without going through floats.
+///
+/// All rounding is half up on the decimal digits of `amount`, so any `U256`
+/// is rendered exactly up to the requested precision.
+pub fn format_token_amount(amount: U256, decimals: u8, style: AmountStyle) -> String {
+    let (integer, fraction) = split_decimal(amount, decimals);
+
+    match style {
+        AmountStyle::Fixed(places) => round_to(&integer, &fraction, places),
+        AmountStyle::Significant(digits) => {
+            let places = if integer != "0" {
+                digits.saturating_sub(integer.len())
+            } else {
+                match fraction.find(|c| c != '0') {
+                    Some(leading_zeros) => leading_zeros + digits,
+                    None => return "0".to_string(),
+                }
+            };
+            round_to(&integer, &fraction, places)
+        }
+        AmountStyle::Compact => format_compact(&integer, &fraction),
+    }
+}
+
+/// Splits the decimal digits of `amount` into integer and fractional parts.
+fn split_decimal(amount: U256, decimals: u8) -> (String, String) {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount.to_string(), width = decimals + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+
+    let integer = integer.trim_start_matches('0');
+    let integer = if integer.is_empty() { "0" } else { integer };
+
+    (integer.to_string(), fraction.to_string())
+}
+
+/// Rounds `integer.fraction` half up to `places` fractional digits.
+fn round_to(integer: &str, fraction: &str, places: usize) -> String {
+    let mut digits: Vec<u8> = integer.bytes().collect();
+    digits.extend(fraction.bytes().take(places));
+    digits.resize(integer.len() + places, b'0');
+
+    if fraction.as_bytes().get(places).is_some_and(|&d| d >= b'5') {
+        let mut carry = true;
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                carry = false;
+                break;
+            }
+        }
+        if carry {
+            digits.insert(0, b'1');
+        }
+    }
+
+    let split = digits.len() - places;
+    let (integer, fraction) = digits.split_at(split);
+    let integer = String::from_utf8_lossy(integer);
+
+    if places == 0 {
+        integer.into_owned()
+    } else {
+        format!("{}.{}", integer, String::from_utf8_lossy(fraction))
+    }
+}
+
+fn format_compact(integer: &str, fraction: &str) -> String {
+    let mut unit = ((integer.len() - 1) / 3).min(COMPACT_SUFFIXES.len() - 1);
+    loop {
+        let shift = integer.len() - unit * 3;
+        let shifted_fraction = format!("{}{}", &integer[shift..], fraction);
+        let head = if shift == 0 { "0" } else { &integer[..shift] };
+        // Amounts below one keep at least four places, and two significant
+        // digits when those are all zeros, so nothing nonzero shows up as 0
+        let places = match fraction.find(|c| c != '0') {
+            Some(leading_zeros) if integer == "0" => (leading_zeros + 2).max(4),
+            _ => 1,
+        };
+        let rounded = round_to(head, &shifted_fraction, places);
+
+        // 999.96K rounds to 1000.0K; promote it to 1.0M instead
+        let integer_digits = rounded.find('.').unwrap_or(rounded.len());
+        if integer_digits > 3 && unit + 1 < COMPACT_SUFFIXES.len() {
+            unit += 1;
+            continue;
+        }
+
+        return format!("{}{}", trim_fraction(rounded), COMPACT_SUFFIXES[unit]);
+    }
+}
+
+fn trim_fraction(value: String) -> String {
+    if !value.contains('.') {
+        return value;
+    }
+    value
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `value` scaled to a raw amount with 18 decimals.
+    fn tokens(value: &str) -> U256 {
+        let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+        format!("{}{:0<18}", integer, fraction).parse().unwrap()
+    }
+
+    fn format(value: &str, style: AmountStyle) -> String {
+        format_token_amount(tokens(value), 18, style)
+    }
+
+    #[test]
+    fn fixed() {
+        assert_eq!(format("0", AmountStyle::Fixed(0)), "0");
+        assert_eq!(format("0", AmountStyle::Fixed(2)), "0.00");
+        assert_eq!(format("999.96", AmountStyle::Fixed(2)), "999.96");
+        assert_eq!(format("999.96", AmountStyle::Fixed(1)), "1000.0");
+        assert_eq!(format("999.96", AmountStyle::Fixed(0)), "1000");
+        assert_eq!(format("999960", AmountStyle::Fixed(0)), "999960");
+        assert_eq!(
+            format("0.000000000000000001", AmountStyle::Fixed(18)),
+            "0.000000000000000001"
+        );
+    }
+
+    #[test]
+    fn significant() {
+        assert_eq!(format("0", AmountStyle::Significant(8)), "0");
+        assert_eq!(format("0.0012345", AmountStyle::Significant(3)), "0.00123");
+        assert_eq!(format("999.96", AmountStyle::Significant(8)), "999.96000");
+        assert_eq!(format("999.96", AmountStyle::Significant(4)), "1000.0");
+        // The integer part is never rounded away
+        assert_eq!(format("999960", AmountStyle::Significant(3)), "999960");
+    }
+
+    #[test]
+    fn compact() {
+        assert_eq!(format("0", AmountStyle::Compact), "0");
+        assert_eq!(format("0.12345", AmountStyle::Compact), "0.1235");
+        assert_eq!(format("0.00012", AmountStyle::Compact), "0.00012");
+        assert_eq!(format("0.0000123", AmountStyle::Compact), "0.000012");
+        assert_eq!(
+            format("0.000000000000000001", AmountStyle::Compact),
+            "0.000000000000000001"
+        );
+        assert_eq!(format("999.94", AmountStyle::Compact), "999.9");
+        assert_eq!(format("999.96", AmountStyle::Compact), "1K");
+        assert_eq!(format("1500", AmountStyle::Compact), "1.5K");
+        assert_eq!(format("999940", AmountStyle::Compact), "999.9K");
+        assert_eq!(format("999960", AmountStyle::Compact), "1M");
+        assert_eq!(format("999960000000", AmountStyle::Compact), "1T");
+    }
+
+    #[test]
+    fn near_u256_max() {
+        let max = U256::MAX.to_string();
+
+        assert_eq!(
+            format_token_amount(U256::MAX, 0, AmountStyle::Significant(8)),
+            max
+        );
+        assert_eq!(
+            format_token_amount(U256::MAX, 18, AmountStyle::Fixed(2)),
+            format!("{}.{}", &max[..60], &max[60..62])
+        );
+        // Rounding the last digit carries through the whole integer part
+        assert_eq!(
+            format_token_amount(U256::MAX, 1, AmountStyle::Fixed(0)),
+            "11579208923731619542357098500868790785326998466564056403945758400791312963994"
+        );
+        assert_eq!(
+            format_token_amount(U256::MAX, 0, AmountStyle::Compact),
+            format!("{}.9T", &max[..66])
+        );
+        assert_eq!(
+            format_token_amount(U256::MAX - U256::from(1u8), 0, AmountStyle::Fixed(3)),
+            format!("{}4.000", &max[..77])
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use alloy::sol;
+
+sol! {
+    #[sol(rpc)]
+    contract UniswapV2Pair {
+        // Sync event - reserve changes after each swap
+        event Sync(uint112 reserve0, uint112 reserve1);
+
+        // Swap event - for volume calculation
+        event Swap(
+            address indexed sender,
+            uint256 amount0In,
+            uint256 amount1In,
+            uint256 amount0Out,
+            uint256 amount1Out,
+            address indexed to
+        );
+
+        function token0() external view returns (address);
+        function token1() external view returns (address);
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+    }
+
+    #[sol(rpc)]
+    contract UniswapV3Pool {
+        // Swap event - signed amounts are the pool's balance deltas
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick
+        );
+
+        struct Slot0 {
+            // the current price
+            uint160 sqrtPriceX96;
+            // the current tick
+            int24 tick;
+            // the most-recently updated index of the observations array
+            uint16 observationIndex;
+            // the current maximum number of observations that are being stored
+            uint16 observationCardinality;
+            // the next maximum number of observations to store, triggered in observations.write
+            uint16 observationCardinalityNext;
+            // the current protocol fee as a percentage of the swap fee taken on withdrawal
+            // represented as an integer denominator (1/x)%
+            uint8 feeProtocol;
+            // whether the pool is locked
+            bool unlocked;
+        }
+
+        function token0() external view returns (address);
+        function token1() external view returns (address);
+
+        function slot0() external view returns (
+            uint160 sqrtPriceX96,
+            int24 tick,
+            uint16 observationIndex,
+            uint16 observationCardinality,
+            uint16 observationCardinalityNext,
+            uint8 feeProtocol,
+            bool unlocked
+        );
+
+        function observations(uint256 index) external view returns (
+            uint32 blockTimestamp,
+            int56 tickCumulative,
+            uint160 secondsPerLiquidityCumulativeX128,
+            bool initialized
+        );
+
+        function observe(uint32[] calldata secondsAgos) external view returns (
+            int56[] memory tickCumulatives,
+            uint160[] memory secondsPerLiquidityCumulativeX128s
+        );
+    }
+
+    #[sol(rpc)]
+    contract ERC20 {
+        function decimals() external view returns (uint8);
+        function symbol() external view returns (string);
+    }
+}
@@ -0,0 +1,16 @@
+use std::{fs, io, path::Path};
+
+/// Replaces the cache file at `path` with `contents`, creating its directory
+/// if needed.
+///
+/// The contents are written to a temporary file first and renamed over the
+/// old one, so a crash never leaves a truncated cache behind.
+pub(crate) fn write_cache_file(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
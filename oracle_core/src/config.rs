@@ -0,0 +1,593 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use alloy::primitives::Address;
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use serde::Deserialize;
+
+use crate::{
+    pool::{Orientation, Pool, ProtocolVersion},
+    registry::TokenRegistry,
+    timestamps::BlockTimestamps,
+    token::Token,
+};
+
+/// Chains and pools shared by all oracle tools.
+///
+/// ```toml
+/// [chains.mainnet]
+/// chain_id = 1
+/// rpc_url = "https://mainnet.gateway.tenderly.co"
+///
+/// [[pools]]
+/// name = "usdc-weth-v3"
+/// chain = "mainnet"
+/// address = "0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8"
+/// version = "v3"
+/// base = "WETH"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// JSON store for token metadata, shared by all tools.
+    #[serde(default = "default_token_cache")]
+    pub token_cache: PathBuf,
+    /// Directory holding one block timestamp cache file per chain.
+    #[serde(default = "default_block_timestamp_cache")]
+    pub block_timestamp_cache: PathBuf,
+    pub chains: BTreeMap<String, ChainConfig>,
+    #[serde(default)]
+    pub pools: Vec<PoolConfig>,
+    /// Where streamed swaps are written and stored candles are read from;
+    /// swaps are only printed without it.
+    pub questdb: Option<QuestDbConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub rpc_url: String,
+    /// Endpoint used for log subscriptions; falls back to `rpc_url`.
+    pub ws_url: Option<String>,
+}
+
+impl ChainConfig {
+    pub fn ws_url(&self) -> &str {
+        self.ws_url.as_deref().unwrap_or(&self.rpc_url)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    pub name: String,
+    pub chain: String,
+    pub address: Address,
+    pub version: ProtocolVersion,
+    /// Token prices are quoted for, by symbol or address; defaults to
+    /// token0, or to the token that is not `quote`.
+    pub base: Option<String>,
+    /// Token prices are quoted in, by symbol or address.
+    pub quote: Option<String>,
+}
+
+impl PoolConfig {
+    /// Orientation declared by `base` and `quote`, checked against the
+    /// pool's actual tokens.
+    pub fn orientation(&self, pool: &Pool) -> Result<Orientation> {
+        // The orientation in which the referenced token is the base
+        let as_base = |reference: &Option<String>, role: &str| -> Result<Option<Orientation>> {
+            let Some(reference) = reference else {
+                return Ok(None);
+            };
+            if refers_to(&pool.token0, reference) {
+                Ok(Some(Orientation::Token0Base))
+            } else if refers_to(&pool.token1, reference) {
+                Ok(Some(Orientation::Token1Base))
+            } else {
+                bail!(
+                    "{} `{}` of pool `{}` is neither {} nor {}",
+                    role,
+                    reference,
+                    self.name,
+                    pool.token0.symbol,
+                    pool.token1.symbol
+                )
+            }
+        };
+
+        match (as_base(&self.base, "base")?, as_base(&self.quote, "quote")?) {
+            (Some(base), Some(quote)) if base == quote => {
+                bail!("base and quote of pool `{}` are the same token", self.name)
+            }
+            (Some(base), _) => Ok(base),
+            (None, Some(quote)) => Ok(quote.inverse()),
+            (None, None) => Ok(Orientation::default()),
+        }
+    }
+}
+
+/// Whether `reference` is the token's address or, ignoring case, its symbol.
+fn refers_to(token: &Token, reference: &str) -> bool {
+    match reference.parse::<Address>() {
+        Ok(address) => address == token.address,
+        Err(_) => token.symbol.eq_ignore_ascii_case(reference),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuestDbConfig {
+    /// ILP client configuration string swaps are written with.
+    #[serde(default = "default_questdb_ilp")]
+    pub ilp: String,
+    /// Base URL of the HTTP query interface candles are read from.
+    #[serde(default = "default_questdb_http_url")]
+    pub http_url: String,
+    #[serde(default = "default_questdb_table")]
+    pub table: String,
+}
+
+impl Default for QuestDbConfig {
+    fn default() -> Self {
+        Self {
+            ilp: default_questdb_ilp(),
+            http_url: default_questdb_http_url(),
+            table: default_questdb_table(),
+        }
+    }
+}
+
+/// Command line flags every tool accepts for locating and narrowing its config.
+#[derive(Debug, Clone, Args)]
+pub struct ConfigArgs {
+    /// Path to the TOML configuration file
+    #[arg(long, env = "ORACLE_CONFIG", default_value = "oracle.toml")]
+    pub config: PathBuf,
+
+    /// Only use the named pool(s); defaults to every pool the tool supports
+    #[arg(long = "pool", value_name = "NAME")]
+    pub pools: Vec<String>,
+
+    /// Override the RPC endpoint of a chain, as `<chain>=<url>`; a bare URL
+    /// applies to the chain of the selected pools, which must then all be
+    /// on one chain. May be repeated
+    #[arg(long = "rpc-url", env = "ORACLE_RPC_URL", value_name = "[CHAIN=]URL")]
+    pub rpc_urls: Vec<String>,
+
+    /// Override the WebSocket endpoint of a chain, like --rpc-url
+    #[arg(long = "ws-url", env = "ORACLE_WS_URL", value_name = "[CHAIN=]URL")]
+    pub ws_urls: Vec<String>,
+
+    /// Write streamed swaps to QuestDB using this ILP configuration string
+    #[arg(long, env = "ORACLE_QUESTDB_ILP", value_name = "CONF")]
+    pub questdb_ilp: Option<String>,
+
+    /// Read candles from the QuestDB HTTP query interface at this URL
+    #[arg(long, env = "ORACLE_QUESTDB_URL", value_name = "URL")]
+    pub questdb_url: Option<String>,
+
+    /// Report prices as base per quote, the inverse of each pool's
+    /// configured orientation; swaps written to QuestDB keep the configured
+    /// one
+    #[arg(long)]
+    pub invert: bool,
+}
+
+impl ConfigArgs {
+    /// Loads the config file, applies environment and flag overrides and
+    /// validates the result.
+    pub fn load(&self) -> Result<Config> {
+        let mut config = Config::from_file(&self.config)?;
+        config.apply_env_overrides();
+
+        override_endpoints(&mut config, &self.rpc_urls, |chain, url| {
+            chain.rpc_url = url.to_string();
+        })?;
+        override_endpoints(&mut config, &self.ws_urls, |chain, url| {
+            chain.ws_url = Some(url.to_string());
+        })?;
+
+        if let Some(ilp) = &self.questdb_ilp {
+            config.questdb.get_or_insert_default().ilp = ilp.clone();
+        }
+        if let Some(http_url) = &self.questdb_url {
+            config.questdb.get_or_insert_default().http_url = http_url.clone();
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Orients `pool` as configured for it, inverted with `--invert`.
+    pub fn orient(&self, pool_config: &PoolConfig, pool: &mut Pool) -> Result<()> {
+        let orientation = pool_config.orientation(pool)?;
+        pool.orientation = if self.invert {
+            orientation.inverse()
+        } else {
+            orientation
+        };
+        Ok(())
+    }
+
+    /// Pools selected with `--pool` (or all of them) that use `version`.
+    pub fn select_pools<'a>(
+        &self,
+        config: &'a Config,
+        version: ProtocolVersion,
+    ) -> Result<Vec<&'a PoolConfig>> {
+        let pools: Vec<&PoolConfig> = if self.pools.is_empty() {
+            config
+                .pools
+                .iter()
+                .filter(|pool| pool.version == version)
+                .collect()
+        } else {
+            self.pools
+                .iter()
+                .map(|name| {
+                    let pool = config.pool(name)?;
+                    if pool.version != version {
+                        bail!(
+                            "pool `{}` is {:?}, expected {:?}",
+                            name,
+                            pool.version,
+                            version
+                        );
+                    }
+                    Ok(pool)
+                })
+                .collect::<Result<_>>()?
+        };
+
+        if pools.is_empty() {
+            bail!(
+                "no {:?} pools configured in {}",
+                version,
+                self.config.display()
+            );
+        }
+
+        // A bare endpoint overrides every chain, which is only right when a
+        // single one is in use
+        let unscoped = self
+            .rpc_urls
+            .iter()
+            .chain(&self.ws_urls)
+            .any(|value| split_endpoint(value).0.is_none());
+        let chains: BTreeSet<&str> = pools.iter().map(|pool| pool.chain.as_str()).collect();
+        if unscoped && chains.len() > 1 {
+            bail!(
+                "the selected pools are on chains {}, so --rpc-url and --ws-url need a chain, \
+                 e.g. --rpc-url {}=<url>",
+                chains.iter().copied().collect::<Vec<_>>().join(", "),
+                chains.first().unwrap()
+            );
+        }
+
+        Ok(pools)
+    }
+}
+
+/// Splits an endpoint override into the chain it is scoped to, if any, and
+/// the URL. A URL's scheme keeps it from being read as a chain name.
+fn split_endpoint(value: &str) -> (Option<&str>, &str) {
+    match value.split_once('=') {
+        Some((chain, url)) if !chain.contains([':', '/']) => (Some(chain), url),
+        _ => (None, value),
+    }
+}
+
+/// Applies `[chain=]url` overrides with `set`; bare URLs go to every chain.
+fn override_endpoints(
+    config: &mut Config,
+    values: &[String],
+    set: impl Fn(&mut ChainConfig, &str),
+) -> Result<()> {
+    for value in values {
+        match split_endpoint(value) {
+            (Some(name), url) => {
+                let chain = config
+                    .chains
+                    .get_mut(name)
+                    .with_context(|| format!("unknown chain `{}` in `{}`", name, value))?;
+                set(chain, url);
+            }
+            (None, url) => {
+                for chain in config.chains.values_mut() {
+                    set(chain, url);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn default_token_cache() -> PathBuf {
+    PathBuf::from("token_cache.json")
+}
+
+fn default_block_timestamp_cache() -> PathBuf {
+    PathBuf::from("block_timestamps")
+}
+
+fn default_questdb_ilp() -> String {
+    "tcp::addr=localhost:9009;".to_string()
+}
+
+fn default_questdb_http_url() -> String {
+    "http://localhost:9000".to_string()
+}
+
+fn default_questdb_table() -> String {
+    "trades".to_string()
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config file {}", path.display()))
+    }
+
+    /// Applies `ORACLE_<CHAIN>_RPC_URL` and `ORACLE_<CHAIN>_WS_URL` overrides,
+    /// where `<CHAIN>` is the upper-cased chain name.
+    pub fn apply_env_overrides(&mut self) {
+        for (name, chain) in self.chains.iter_mut() {
+            let prefix = format!("ORACLE_{}", name.to_uppercase().replace('-', "_"));
+
+            if let Ok(rpc_url) = std::env::var(format!("{}_RPC_URL", prefix)) {
+                chain.rpc_url = rpc_url;
+            }
+            if let Ok(ws_url) = std::env::var(format!("{}_WS_URL", prefix)) {
+                chain.ws_url = Some(ws_url);
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (name, chain) in &self.chains {
+            if chain.rpc_url.is_empty() {
+                bail!("chain `{}` has an empty rpc_url", name);
+            }
+        }
+
+        let mut names = HashSet::new();
+        for pool in &self.pools {
+            if !names.insert(pool.name.as_str()) {
+                bail!("duplicate pool name `{}`", pool.name);
+            }
+            if !self.chains.contains_key(&pool.chain) {
+                bail!(
+                    "pool `{}` refers to unknown chain `{}`",
+                    pool.name,
+                    pool.chain
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn pool(&self, name: &str) -> Result<&PoolConfig> {
+        self.pools
+            .iter()
+            .find(|pool| pool.name == name)
+            .with_context(|| format!("unknown pool `{}`", name))
+    }
+
+    pub fn token_registry(&self) -> Result<TokenRegistry> {
+        TokenRegistry::open(&self.token_cache)
+    }
+
+    pub fn block_timestamps(&self, chain: &ChainConfig) -> Result<BlockTimestamps> {
+        BlockTimestamps::open(&self.block_timestamp_cache, chain.chain_id)
+    }
+
+    /// Chain the pool lives on; always present once the config is validated.
+    pub fn chain(&self, pool: &PoolConfig) -> &ChainConfig {
+        &self.chains[&pool.chain]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DAI/WETH with DAI as token0.
+    fn pool() -> Pool {
+        Pool {
+            address: Address::repeat_byte(0xaa),
+            version: ProtocolVersion::V2,
+            token0: Token::new(Address::repeat_byte(1), "DAI", 18),
+            token1: Token::new(Address::repeat_byte(2), "WETH", 18),
+            orientation: Orientation::Token0Base,
+        }
+    }
+
+    fn pool_config(base: Option<&str>, quote: Option<&str>) -> PoolConfig {
+        PoolConfig {
+            name: "dai-weth".to_string(),
+            chain: "mainnet".to_string(),
+            address: Address::repeat_byte(0xaa),
+            version: ProtocolVersion::V2,
+            base: base.map(str::to_string),
+            quote: quote.map(str::to_string),
+        }
+    }
+
+    /// Two chains with a V2 pool on each.
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [chains.mainnet]
+            chain_id = 1
+            rpc_url = "https://mainnet.example"
+
+            [chains.arbitrum]
+            chain_id = 42161
+            rpc_url = "https://arbitrum.example"
+
+            [[pools]]
+            name = "dai-weth"
+            chain = "mainnet"
+            address = "0x0101010101010101010101010101010101010101"
+            version = "v2"
+
+            [[pools]]
+            name = "weth-usdc"
+            chain = "arbitrum"
+            address = "0x0202020202020202020202020202020202020202"
+            version = "v2"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn config_args(pools: &[&str], rpc_urls: &[&str]) -> ConfigArgs {
+        ConfigArgs {
+            config: PathBuf::from("oracle.toml"),
+            pools: pools.iter().map(|pool| pool.to_string()).collect(),
+            rpc_urls: rpc_urls.iter().map(|url| url.to_string()).collect(),
+            ws_urls: Vec::new(),
+            questdb_ilp: None,
+            questdb_url: None,
+            invert: false,
+        }
+    }
+
+    fn orientation(base: Option<&str>, quote: Option<&str>) -> Result<Orientation> {
+        pool_config(base, quote).orientation(&pool())
+    }
+
+    #[test]
+    fn defaults_to_token0_as_base() {
+        assert_eq!(orientation(None, None).unwrap(), Orientation::Token0Base);
+    }
+
+    #[test]
+    fn base_or_quote_alone_decides() {
+        assert_eq!(
+            orientation(Some("WETH"), None).unwrap(),
+            Orientation::Token1Base
+        );
+        assert_eq!(
+            orientation(Some("DAI"), None).unwrap(),
+            Orientation::Token0Base
+        );
+        assert_eq!(
+            orientation(None, Some("DAI")).unwrap(),
+            Orientation::Token1Base
+        );
+        assert_eq!(
+            orientation(None, Some("WETH")).unwrap(),
+            Orientation::Token0Base
+        );
+        assert_eq!(
+            orientation(Some("WETH"), Some("DAI")).unwrap(),
+            Orientation::Token1Base
+        );
+    }
+
+    #[test]
+    fn matches_addresses_and_symbols_ignoring_case() {
+        let weth = Address::repeat_byte(2).to_string();
+        assert_eq!(
+            orientation(Some(&weth), None).unwrap(),
+            Orientation::Token1Base
+        );
+        assert_eq!(
+            orientation(Some(&weth.to_lowercase()), None).unwrap(),
+            Orientation::Token1Base
+        );
+        assert_eq!(
+            orientation(Some("weth"), None).unwrap(),
+            Orientation::Token1Base
+        );
+        assert_eq!(
+            orientation(None, Some("Dai")).unwrap(),
+            Orientation::Token1Base
+        );
+    }
+
+    #[test]
+    fn rejects_the_same_token_on_both_sides() {
+        let err = orientation(Some("WETH"), Some("weth")).unwrap_err();
+        assert!(err.to_string().contains("same token"));
+
+        let dai = Address::repeat_byte(1).to_string();
+        assert!(orientation(Some("DAI"), Some(&dai)).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_tokens() {
+        let err = orientation(Some("USDC"), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "base `USDC` of pool `dai-weth` is neither DAI nor WETH"
+        );
+
+        let other = Address::repeat_byte(3).to_string();
+        assert!(orientation(None, Some(&other)).is_err());
+    }
+
+    #[test]
+    fn splits_scoped_endpoints() {
+        assert_eq!(
+            split_endpoint("mainnet=wss://node.example/ws"),
+            (Some("mainnet"), "wss://node.example/ws")
+        );
+        assert_eq!(
+            split_endpoint("https://node.example/?key=abc"),
+            (None, "https://node.example/?key=abc")
+        );
+        assert_eq!(
+            split_endpoint("http://localhost:8545"),
+            (None, "http://localhost:8545")
+        );
+    }
+
+    #[test]
+    fn scoped_overrides_only_touch_their_chain() {
+        let mut config = config();
+        let values = ["arbitrum=http://localhost:8547".to_string()];
+        override_endpoints(&mut config, &values, |chain, url| {
+            chain.rpc_url = url.to_string()
+        })
+        .unwrap();
+
+        assert_eq!(config.chains["mainnet"].rpc_url, "https://mainnet.example");
+        assert_eq!(config.chains["arbitrum"].rpc_url, "http://localhost:8547");
+
+        let unknown = ["optimism=http://localhost:8548".to_string()];
+        assert!(override_endpoints(&mut config, &unknown, |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn bare_overrides_need_a_single_chain() {
+        let config = config();
+        let bare = "http://localhost:8545";
+
+        assert!(
+            config_args(&[], &[bare])
+                .select_pools(&config, ProtocolVersion::V2)
+                .is_err()
+        );
+        assert!(
+            config_args(&["dai-weth"], &[bare])
+                .select_pools(&config, ProtocolVersion::V2)
+                .is_ok()
+        );
+        assert!(
+            config_args(&[], &["mainnet=http://localhost:8545"])
+                .select_pools(&config, ProtocolVersion::V2)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+
+use alloy::{primitives::B256, rpc::types::Log};
+use clap::Args;
+
+/// Blocks kept below the confirmed head so late `removed` logs can still be
+/// matched and retracted.
+const RETAINED_BLOCKS: u64 = 128;
+
+/// When a block is treated as final.
+///
+/// `confirmations` counts the blocks built on top of a log's block; zero
+/// means logs are final as soon as they are seen. Flatten it into a CLI to
+/// get `--confirmations`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Args)]
+pub struct Finality {
+    /// Only treat a log as final once this many blocks are built on top of it
+    #[arg(long, default_value_t = 0)]
+    pub confirmations: u64,
+}
+
+impl Finality {
+    pub fn new(confirmations: u64) -> Self {
+        Self { confirmations }
+    }
+
+    pub fn is_final(&self, block_number: u64, head: u64) -> bool {
+        block_number.saturating_add(self.confirmations) <= head
+    }
+
+    /// Highest block that is final when the chain is at `head`.
+    pub fn final_block(&self, head: u64) -> u64 {
+        head.saturating_sub(self.confirmations)
+    }
+}
+
+/// What a subscriber should do with a log after a [`LogTracker`] update.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogUpdate {
+    /// The log reached the required confirmations.
+    Emit(Log),
+    /// A previously emitted log is no longer part of the canonical chain.
+    Retract(Log),
+}
+
+/// Turns a raw log subscription into emits and retractions.
+///
+/// Logs are held until they reach the configured [`Finality`]. The block hash
+/// seen at each height is tracked, so a log or head on a different hash, or a
+/// log delivered with `removed = true`, retracts the logs that were emitted
+/// from the replaced block. Logs that are reorged out before becoming final
+/// are dropped without ever being emitted.
+#[derive(Debug, Default)]
+pub struct LogTracker {
+    finality: Finality,
+    head: u64,
+    block_hashes: BTreeMap<u64, B256>,
+    pending: BTreeMap<u64, Vec<Log>>,
+    emitted: BTreeMap<u64, Vec<Log>>,
+}
+
+impl LogTracker {
+    pub fn new(finality: Finality) -> Self {
+        Self {
+            finality,
+            ..Self::default()
+        }
+    }
+
+    /// Highest block seen so far, zero before the first log or head.
+    pub fn head(&self) -> u64 {
+        self.head
+    }
+
+    /// Feeds one log from the subscription or a backfill. Logs that were
+    /// already fed are ignored.
+    pub fn push(&mut self, log: Log) -> Vec<LogUpdate> {
+        let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) else {
+            return Vec::new();
+        };
+
+        if log.removed {
+            return self.remove(block_number, block_hash, log.log_index);
+        }
+
+        // Backfills after a reconnect overlap with what was already seen
+        let known = self
+            .pending
+            .get(&block_number)
+            .into_iter()
+            .chain(self.emitted.get(&block_number))
+            .flatten()
+            .any(|seen| seen.block_hash == log.block_hash && seen.log_index == log.log_index);
+        if known {
+            return Vec::new();
+        }
+
+        let mut updates = self.observe_block(block_number, block_hash);
+        self.pending.entry(block_number).or_default().push(log);
+        updates.extend(self.release());
+        updates
+    }
+
+    /// Feeds a new chain head, e.g. from a block subscription.
+    pub fn advance(&mut self, block_number: u64, block_hash: B256) -> Vec<LogUpdate> {
+        let mut updates = self.observe_block(block_number, block_hash);
+        updates.extend(self.release());
+        updates
+    }
+
+    /// Records the hash at `block_number`, retracting logs from a different
+    /// block previously seen at that height.
+    fn observe_block(&mut self, block_number: u64, block_hash: B256) -> Vec<LogUpdate> {
+        self.head = self.head.max(block_number);
+
+        // A height seen for the first time, or again after one of its logs
+        // was removed, may still hold logs of a block that is gone
+        let previous = self.block_hashes.insert(block_number, block_hash);
+        if previous == Some(block_hash) {
+            return Vec::new();
+        }
+
+        if let Some(logs) = self.pending.get_mut(&block_number) {
+            logs.retain(|log| log.block_hash == Some(block_hash));
+        }
+
+        let Some(logs) = self.emitted.get_mut(&block_number) else {
+            return Vec::new();
+        };
+        let (kept, replaced) = logs
+            .drain(..)
+            .partition(|log| log.block_hash == Some(block_hash));
+        *logs = kept;
+
+        replaced.into_iter().map(LogUpdate::Retract).collect()
+    }
+
+    fn remove(
+        &mut self,
+        block_number: u64,
+        block_hash: B256,
+        log_index: Option<u64>,
+    ) -> Vec<LogUpdate> {
+        let matches = |log: &Log| log.block_hash == Some(block_hash) && log.log_index == log_index;
+
+        if let Some(logs) = self.pending.get_mut(&block_number) {
+            logs.retain(|log| !matches(log));
+        }
+        if self.block_hashes.get(&block_number) == Some(&block_hash) {
+            self.block_hashes.remove(&block_number);
+        }
+
+        let Some(logs) = self.emitted.get_mut(&block_number) else {
+            return Vec::new();
+        };
+        match logs.iter().position(matches) {
+            Some(position) => vec![LogUpdate::Retract(logs.remove(position))],
+            None => Vec::new(),
+        }
+    }
+
+    /// Emits every pending log that became final, in chain order.
+    fn release(&mut self) -> Vec<LogUpdate> {
+        let final_block = self.finality.final_block(self.head);
+        let still_pending = self.pending.split_off(&(final_block + 1));
+        let ready = std::mem::replace(&mut self.pending, still_pending);
+
+        let mut updates = Vec::new();
+        for (block_number, mut logs) in ready {
+            logs.sort_by_key(|log| log.log_index);
+            updates.extend(logs.iter().cloned().map(LogUpdate::Emit));
+            self.emitted.entry(block_number).or_default().extend(logs);
+        }
+
+        // Forget blocks too deep to be reorged in practice
+        let cutoff = final_block.saturating_sub(RETAINED_BLOCKS);
+        self.emitted = self.emitted.split_off(&cutoff);
+        self.block_hashes = self.block_hashes.split_off(&cutoff);
+
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> B256 {
+        B256::repeat_byte(byte)
+    }
+
+    fn log(block_number: u64, block_hash: u8, log_index: u64) -> Log {
+        Log {
+            block_number: Some(block_number),
+            block_hash: Some(hash(block_hash)),
+            log_index: Some(log_index),
+            ..Log::default()
+        }
+    }
+
+    fn removed(log: Log) -> Log {
+        Log {
+            removed: true,
+            ..log
+        }
+    }
+
+    #[test]
+    fn emits_immediately_without_confirmations() {
+        let mut tracker = LogTracker::new(Finality::new(0));
+        assert_eq!(
+            tracker.push(log(10, 1, 0)),
+            vec![LogUpdate::Emit(log(10, 1, 0))]
+        );
+        assert_eq!(tracker.head(), 10);
+    }
+
+    #[test]
+    fn emits_once_final_in_log_order() {
+        let mut tracker = LogTracker::new(Finality::new(2));
+        assert!(tracker.push(log(10, 1, 5)).is_empty());
+        assert!(tracker.push(log(10, 1, 2)).is_empty());
+        assert!(tracker.advance(11, hash(2)).is_empty());
+        assert_eq!(
+            tracker.advance(12, hash(3)),
+            vec![
+                LogUpdate::Emit(log(10, 1, 2)),
+                LogUpdate::Emit(log(10, 1, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn retracts_emitted_logs_when_the_hash_at_their_height_changes() {
+        let mut tracker = LogTracker::new(Finality::new(0));
+        tracker.push(log(10, 1, 0));
+        tracker.push(log(10, 1, 1));
+
+        assert_eq!(
+            tracker.advance(10, hash(9)),
+            vec![
+                LogUpdate::Retract(log(10, 1, 0)),
+                LogUpdate::Retract(log(10, 1, 1)),
+            ]
+        );
+        // The replacing block's logs are new
+        assert_eq!(
+            tracker.push(log(10, 9, 0)),
+            vec![LogUpdate::Emit(log(10, 9, 0))]
+        );
+    }
+
+    #[test]
+    fn retracts_logs_delivered_as_removed() {
+        let mut tracker = LogTracker::new(Finality::new(0));
+        tracker.push(log(10, 1, 0));
+        tracker.push(log(10, 1, 1));
+
+        assert_eq!(
+            tracker.push(removed(log(10, 1, 1))),
+            vec![LogUpdate::Retract(log(10, 1, 1))]
+        );
+        assert!(tracker.push(removed(log(10, 1, 1))).is_empty());
+    }
+
+    #[test]
+    fn drops_pending_logs_reorged_out_before_they_are_final() {
+        let mut tracker = LogTracker::new(Finality::new(2));
+        tracker.push(log(10, 1, 0));
+        tracker.push(log(10, 1, 1));
+        tracker.push(removed(log(10, 1, 1)));
+        assert!(tracker.advance(10, hash(9)).is_empty());
+
+        assert!(tracker.advance(12, hash(3)).is_empty());
+    }
+
+    #[test]
+    fn ignores_logs_seen_before() {
+        let mut tracker = LogTracker::new(Finality::new(1));
+        assert!(tracker.push(log(10, 1, 0)).is_empty());
+        assert!(tracker.push(log(10, 1, 0)).is_empty());
+        assert_eq!(
+            tracker.advance(11, hash(2)),
+            vec![LogUpdate::Emit(log(10, 1, 0))]
+        );
+
+        // A backfill after a reconnect delivers the emitted log again
+        assert!(tracker.push(log(10, 1, 0)).is_empty());
+        assert!(tracker.advance(12, hash(3)).is_empty());
+    }
+
+    #[test]
+    fn forgets_blocks_below_the_retained_window() {
+        let mut tracker = LogTracker::new(Finality::new(0));
+        tracker.push(log(10, 1, 0));
+        tracker.push(log(11, 1, 0));
+
+        // Block 10 falls out of the window, block 11 is still in it
+        tracker.advance(11 + RETAINED_BLOCKS, hash(2));
+        assert!(tracker.advance(10, hash(9)).is_empty());
+        assert!(tracker.push(removed(log(10, 1, 0))).is_empty());
+        assert_eq!(
+            tracker.advance(11, hash(9)),
+            vec![LogUpdate::Retract(log(11, 1, 0))]
+        );
+    }
+}
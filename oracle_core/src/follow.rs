@@ -0,0 +1,143 @@
+use std::{fmt, time::Duration};
+
+use alloy::{
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::Filter,
+};
+use anyhow::Result;
+use futures_util::StreamExt;
+
+use crate::{
+    config::ChainConfig,
+    finality::{Finality, LogTracker, LogUpdate},
+    logs::LogFetcher,
+};
+
+/// First delay before reconnecting a dropped subscription; doubles on every
+/// failed attempt up to `MAX_RETRY_DELAY`.
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Exponential backoff between the sessions of a long-lived subscription.
+///
+/// The delay doubles after every session that ends, up to a minute, and
+/// starts over at one second once [`reset`](Self::reset) reports a working
+/// connection.
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: MIN_RETRY_DELAY,
+        }
+    }
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks the connection as established, so the next drop is retried
+    /// quickly.
+    pub fn reset(&mut self) {
+        self.delay = MIN_RETRY_DELAY;
+    }
+
+    /// Reports why the session of `label` ended, then waits before the next
+    /// attempt.
+    pub async fn retry(&mut self, label: impl fmt::Display, ended: Result<()>) {
+        match ended {
+            Ok(()) => eprintln!(
+                "{}: subscription closed, reconnecting in {:?}",
+                label, self.delay
+            ),
+            Err(err) => eprintln!("{}: {:#}, reconnecting in {:?}", label, err, self.delay),
+        }
+
+        tokio::time::sleep(self.delay).await;
+        self.delay = (self.delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Follows the logs matching `filter` over the chain's WebSocket endpoint
+/// and hands every emit and retraction to `on_updates`.
+///
+/// Logs pass through a [`LogTracker`] that outlives every connection, so a
+/// dropped subscription is reconnected with exponential backoff and resumes
+/// right after the last block that was processed. Never returns on its own.
+pub async fn follow_logs<F>(
+    chain: &ChainConfig,
+    filter: &Filter,
+    finality: Finality,
+    mut on_updates: F,
+) -> Result<()>
+where
+    F: FnMut(Vec<LogUpdate>),
+{
+    let mut tracker = LogTracker::new(finality);
+    let mut backoff = Backoff::new();
+
+    loop {
+        let ended = session(chain, filter, &mut tracker, &mut backoff, &mut on_updates).await;
+        backoff
+            .retry(format!("chain {}", chain.chain_id), ended)
+            .await;
+    }
+}
+
+/// One WebSocket session: subscribes, backfills anything missed since the
+/// tracker's head with `eth_getLogs`, then follows the subscription until it
+/// ends.
+async fn session<F>(
+    chain: &ChainConfig,
+    filter: &Filter,
+    tracker: &mut LogTracker,
+    backoff: &mut Backoff,
+    on_updates: &mut F,
+) -> Result<()>
+where
+    F: FnMut(Vec<LogUpdate>),
+{
+    let ws = WsConnect::new(chain.ws_url());
+    let provider = ProviderBuilder::new().connect_ws(ws).await?;
+
+    // Subscribe before backfilling so nothing falls between the two; the
+    // tracker drops logs that show up in both
+    let mut logs = provider.subscribe_logs(filter).await?.into_stream();
+    // New heads confirm pending logs and expose reorgs of blocks without
+    // any of our logs in them
+    let mut heads = provider.subscribe_blocks().await?.into_stream();
+    backoff.reset();
+
+    if tracker.head() > 0 {
+        let from_block = tracker.head();
+        let to_block = provider.get_block_number().await?;
+        let missed = LogFetcher::new()
+            .fetch(&provider, filter, from_block, to_block)
+            .await?;
+
+        let updates = missed.into_iter().flat_map(|log| tracker.push(log));
+        on_updates(updates.collect());
+    }
+
+    // Logs are handled in arrival order so events from different pools
+    // interleave exactly as the node delivers them
+    loop {
+        let updates = tokio::select! {
+            log = logs.next() => match log {
+                Some(log) => tracker.push(log),
+                None => return Ok(()),
+            },
+            head = heads.next() => match head {
+                Some(head) => tracker.advance(head.number, head.hash),
+                None => return Ok(()),
+            },
+        };
+
+        on_updates(updates);
+    }
+}
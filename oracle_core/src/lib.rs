@@ -0,0 +1,39 @@
+//! Shared models, contract bindings and price helpers used by the oracle binaries.
+
+pub mod amount;
+pub mod bindings;
+mod cache_file;
+pub mod config;
+pub mod finality;
+pub mod follow;
+pub mod logs;
+pub mod multicall;
+pub mod pool;
+pub mod price;
+pub mod questdb;
+pub mod registry;
+pub mod snapshot;
+pub mod tick_math;
+pub mod timestamps;
+pub mod token;
+pub mod trade;
+pub mod twap;
+
+pub use amount::{AmountStyle, format_token_amount, token_amount_to_f64};
+pub use bindings::{ERC20, UniswapV2Pair, UniswapV3Pool};
+pub use config::{ChainConfig, Config, ConfigArgs, PoolConfig, QuestDbConfig};
+pub use finality::{Finality, LogTracker, LogUpdate};
+pub use follow::{Backoff, follow_logs};
+pub use logs::LogFetcher;
+pub use multicall::Multicall;
+pub use pool::{Orientation, Pool, ProtocolVersion};
+pub use price::{
+    DEFAULT_PRICE_PRECISION, Price, PriceQuote, calculate_price_v2, calculate_price_v3,
+};
+pub use questdb::{QuestDbSink, SwapRow, SwapWriter};
+pub use registry::TokenRegistry;
+pub use snapshot::{PoolState, snapshot_pools};
+pub use timestamps::BlockTimestamps;
+pub use token::Token;
+pub use trade::{Side, TRADE_AMOUNT_STYLE};
+pub use twap::{Twap, observe_twap};
@@ -0,0 +1,319 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use alloy::{
+    providers::Provider,
+    rpc::types::{Filter, Log},
+    transports::{RpcError, TransportErrorKind},
+};
+use anyhow::{Context, Result, bail};
+use futures_util::{StreamExt, stream::FuturesUnordered};
+
+/// Error fragments public RPCs use when a `eth_getLogs` range or result set
+/// is too large.
+const RANGE_LIMIT_ERRORS: [&str; 9] = [
+    "too many results",
+    "query returned more than",
+    "block range",
+    "range is too large",
+    "range too large",
+    "exceed maximum block range",
+    "response size exceeded",
+    "logs matched by query exceeds limit",
+    "query timeout",
+];
+
+/// Error fragments of RPCs throttling requests; the request itself is fine
+/// and is sent again unchanged.
+const RATE_LIMIT_ERRORS: [&str; 4] = [
+    "limit exceeded",
+    "rate limit",
+    "too many requests",
+    "exceeded the rate",
+];
+
+/// First wait before resending a rate-limited request; doubles on every
+/// further attempt.
+const RATE_LIMIT_DELAY: Duration = Duration::from_millis(500);
+
+/// Rate-limited attempts of one range before giving up.
+const RATE_LIMIT_RETRIES: u32 = 6;
+
+/// `eth_getLogs` over long block ranges.
+///
+/// The range is split into chunks that are fetched concurrently. A chunk the
+/// node rejects as too large is halved and retried, later chunks start from
+/// the reduced size, and every successful chunk lets the size grow back
+/// toward `max_chunk`. A chunk the node rate-limits is sent again after an
+/// exponential backoff. Results are merged in block order.
+#[derive(Debug, Clone)]
+pub struct LogFetcher {
+    chunk: u64,
+    min_chunk: u64,
+    max_chunk: u64,
+    concurrency: usize,
+}
+
+impl Default for LogFetcher {
+    fn default() -> Self {
+        Self {
+            chunk: 2_000,
+            min_chunk: 1,
+            max_chunk: 10_000,
+            concurrency: 4,
+        }
+    }
+}
+
+impl LogFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Blocks per request to start with; also raises the growth cap if needed.
+    pub fn chunk_size(mut self, blocks: u64) -> Self {
+        self.chunk = blocks.max(1);
+        self.max_chunk = self.max_chunk.max(self.chunk);
+        self
+    }
+
+    /// Upper bound the chunk size grows back to after successful requests.
+    pub fn max_chunk_size(mut self, blocks: u64) -> Self {
+        self.max_chunk = blocks.max(1);
+        self.chunk = self.chunk.min(self.max_chunk);
+        self
+    }
+
+    /// Requests in flight at once.
+    pub fn concurrency(mut self, requests: usize) -> Self {
+        self.concurrency = requests.max(1);
+        self
+    }
+
+    /// All logs matching `filter` between `from_block` and `to_block`
+    /// inclusive, ordered by block.
+    pub async fn fetch<P: Provider>(
+        &self,
+        provider: &P,
+        filter: &Filter,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Log>> {
+        let mut chunk = self.chunk;
+        let mut next_start = from_block;
+        // Ranges to fetch again, with how often each was rate-limited
+        let mut retries: Vec<((u64, u64), u32)> = Vec::new();
+        let mut in_flight = FuturesUnordered::new();
+        let mut results: BTreeMap<u64, Vec<Log>> = BTreeMap::new();
+
+        loop {
+            while in_flight.len() < self.concurrency {
+                let (range, attempt) = if let Some(retry) = retries.pop() {
+                    retry
+                } else if next_start <= to_block {
+                    let end = next_start.saturating_add(chunk - 1).min(to_block);
+                    let range = (next_start, end);
+                    next_start = end + 1;
+                    (range, 0)
+                } else {
+                    break;
+                };
+
+                let range_filter = filter.clone().from_block(range.0).to_block(range.1);
+                in_flight.push(async move {
+                    if attempt > 0 {
+                        tokio::time::sleep(RATE_LIMIT_DELAY * 2u32.pow(attempt - 1)).await;
+                    }
+                    (range, attempt, provider.get_logs(&range_filter).await)
+                });
+            }
+
+            let Some(((start, end), attempt, result)) = in_flight.next().await else {
+                break;
+            };
+
+            match result {
+                Ok(logs) => {
+                    results.insert(start, logs);
+                    chunk = (chunk * 2).min(self.max_chunk);
+                }
+                Err(err) if is_range_limit_error(&err) && end > start => {
+                    let mid = start + (end - start) / 2;
+                    // Pushed in reverse so the lower half is retried first
+                    retries.push(((mid + 1, end), 0));
+                    retries.push(((start, mid), 0));
+                    chunk = (mid - start + 1).clamp(self.min_chunk, self.max_chunk);
+                }
+                Err(err) if is_rate_limit_error(&err) => {
+                    if attempt == RATE_LIMIT_RETRIES {
+                        bail!(
+                            "eth_getLogs for blocks {}..={} still rate-limited after {} retries: {}",
+                            start,
+                            end,
+                            RATE_LIMIT_RETRIES,
+                            err
+                        );
+                    }
+                    retries.push(((start, end), attempt + 1));
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!("eth_getLogs failed for blocks {}..={}", start, end)
+                    });
+                }
+            }
+        }
+
+        Ok(results.into_values().flatten().collect())
+    }
+}
+
+fn is_range_limit_error(err: &RpcError<TransportErrorKind>) -> bool {
+    let message = err.to_string().to_lowercase();
+    RANGE_LIMIT_ERRORS
+        .iter()
+        .any(|fragment| message.contains(fragment))
+}
+
+fn is_rate_limit_error(err: &RpcError<TransportErrorKind>) -> bool {
+    if let RpcError::Transport(TransportErrorKind::HttpError(http)) = err
+        && http.status == 429
+    {
+        return true;
+    }
+    // -32005 is the "limit exceeded" code used by Infura and others; range
+    // errors share it but are told apart by their message first
+    if err.as_error_resp().is_some_and(|resp| resp.code == -32005) {
+        return true;
+    }
+
+    let message = err.to_string().to_lowercase();
+    RATE_LIMIT_ERRORS
+        .iter()
+        .any(|fragment| message.contains(fragment))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::{Address, LogData},
+        providers::{ProviderBuilder, RootProvider},
+        transports::mock::Asserter,
+    };
+
+    use super::*;
+
+    fn provider() -> (RootProvider, Asserter) {
+        let asserter = Asserter::new();
+        let provider = ProviderBuilder::default().connect_mocked_client(asserter.clone());
+        (provider, asserter)
+    }
+
+    /// One log per block of `from..=to`, as a node would answer for it.
+    fn logs(from: u64, to: u64) -> Vec<Log> {
+        (from..=to)
+            .map(|block_number| Log {
+                inner: alloy::primitives::Log {
+                    address: Address::ZERO,
+                    data: LogData::default(),
+                },
+                block_number: Some(block_number),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn block_numbers(logs: &[Log]) -> Vec<u64> {
+        logs.iter().filter_map(|log| log.block_number).collect()
+    }
+
+    fn push_rate_limited(asserter: &Asserter) {
+        let error = serde_json::json!({ "code": -32005, "message": "limit exceeded" });
+        asserter.push_failure(serde_json::from_value(error).unwrap());
+    }
+
+    #[tokio::test]
+    async fn halves_rejected_ranges() {
+        let (provider, asserter) = provider();
+        asserter.push_failure_msg("block range is too large");
+        asserter.push_success(&logs(0, 4));
+        asserter.push_success(&logs(5, 9));
+
+        let fetched = LogFetcher::new()
+            .chunk_size(10)
+            .concurrency(1)
+            .fetch(&provider, &Filter::new(), 0, 9)
+            .await
+            .unwrap();
+
+        assert_eq!(block_numbers(&fetched), (0..=9).collect::<Vec<_>>());
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test]
+    async fn chunk_grows_back_after_successes() {
+        let (provider, asserter) = provider();
+        // 0..=19 is split in two, after which each success doubles the
+        // chunk, so 20..=39 goes out as a single request
+        asserter.push_failure_msg("query returned more than 10000 results");
+        asserter.push_success(&logs(0, 9));
+        asserter.push_success(&logs(10, 19));
+        asserter.push_success(&logs(20, 39));
+
+        let fetched = LogFetcher::new()
+            .chunk_size(20)
+            .concurrency(1)
+            .fetch(&provider, &Filter::new(), 0, 39)
+            .await
+            .unwrap();
+
+        assert_eq!(block_numbers(&fetched), (0..=39).collect::<Vec<_>>());
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_rate_limited_ranges_and_merges_in_block_order() {
+        let (provider, asserter) = provider();
+        // 0..=4 is throttled and only completes after 5..=9
+        push_rate_limited(&asserter);
+        asserter.push_success(&logs(5, 9));
+        asserter.push_success(&logs(0, 4));
+
+        let fetched = LogFetcher::new()
+            .chunk_size(5)
+            .max_chunk_size(5)
+            .concurrency(2)
+            .fetch(&provider, &Filter::new(), 0, 9)
+            .await
+            .unwrap();
+
+        assert_eq!(block_numbers(&fetched), (0..=9).collect::<Vec<_>>());
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_on_persistent_rate_limits() {
+        let (provider, asserter) = provider();
+        for _ in 0..=RATE_LIMIT_RETRIES {
+            push_rate_limited(&asserter);
+        }
+
+        let result = LogFetcher::new()
+            .concurrency(1)
+            .fetch(&provider, &Filter::new(), 0, 9)
+            .await;
+
+        assert!(result.is_err());
+        assert!(asserter.read_q().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_on_other_errors() {
+        let (provider, asserter) = provider();
+        asserter.push_failure_msg("execution reverted");
+
+        let result = LogFetcher::new()
+            .fetch(&provider, &Filter::new(), 0, 9)
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, Bytes, address},
+    providers::Provider,
+    rpc::types::TransactionRequest,
+    sol,
+    sol_types::SolCall,
+};
+use anyhow::Result;
+use futures_util::future::try_join_all;
+
+/// Multicall3 is deployed at the same address on every major chain.
+pub const MULTICALL3_ADDRESS: Address = address!("0xcA11bde05977b3631167028862bE2a173976CA11");
+
+/// Calls per `aggregate3` request; larger batches are split and sent concurrently.
+pub const MAX_CALLS_PER_BATCH: usize = 500;
+
+sol! {
+    contract IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct CallResult {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (CallResult[] memory returnData);
+    }
+}
+
+/// A batch of read-only calls executed through Multicall3 `aggregate3`.
+///
+/// Every call is sent with `allowFailure = true`, so a revert in one call
+/// only turns its own result into `None`.
+#[derive(Clone, Default)]
+pub struct Multicall {
+    calls: Vec<IMulticall3::Call3>,
+}
+
+impl Multicall {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Queues `call` against `target` and returns its index in the results.
+    pub fn add<C: SolCall>(&mut self, target: Address, call: &C) -> usize {
+        self.calls.push(IMulticall3::Call3 {
+            target,
+            allowFailure: true,
+            callData: call.abi_encode().into(),
+        });
+        self.calls.len() - 1
+    }
+
+    /// Executes all queued calls at `block`, returning the raw return data of
+    /// each call in insertion order, or `None` for calls that failed.
+    pub async fn call<P: Provider>(
+        &self,
+        provider: &P,
+        block: BlockId,
+    ) -> Result<Vec<Option<Bytes>>> {
+        let batches = self
+            .calls
+            .chunks(MAX_CALLS_PER_BATCH)
+            .map(|calls| async move {
+                let input = IMulticall3::aggregate3Call {
+                    calls: calls.to_vec(),
+                }
+                .abi_encode();
+                let tx = TransactionRequest::default()
+                    .to(MULTICALL3_ADDRESS)
+                    .input(Bytes::from(input).into());
+
+                let output = provider.call(tx).block(block).await?;
+                let results = IMulticall3::aggregate3Call::abi_decode_returns(&output)?;
+
+                Ok::<_, anyhow::Error>(results)
+            });
+
+        let results = try_join_all(batches).await?;
+
+        Ok(results
+            .into_iter()
+            .flatten()
+            .map(|result| {
+                (result.success && !result.returnData.is_empty()).then_some(result.returnData)
+            })
+            .collect())
+    }
+}
+
+/// Decodes the return value of `C` from one multicall result.
+pub fn decode<C: SolCall>(output: &Option<Bytes>) -> Option<C::Return> {
+    output
+        .as_ref()
+        .and_then(|data| C::abi_decode_returns(data).ok())
+}
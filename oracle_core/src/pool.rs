@@ -0,0 +1,148 @@
+use alloy::{eips::BlockId, primitives::Address, providers::Provider};
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+use crate::{
+    bindings::UniswapV2Pair,
+    multicall::{Multicall, decode},
+    price::{Price, PriceQuote},
+    registry::TokenRegistry,
+    token::Token,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtocolVersion {
+    V2,
+    V3,
+}
+
+/// Which of a pool's tokens is the base that prices are quoted for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// token1 per token0, the way the pool itself stores prices.
+    #[default]
+    Token0Base,
+    /// token0 per token1.
+    Token1Base,
+}
+
+impl Orientation {
+    pub fn inverse(self) -> Self {
+        match self {
+            Orientation::Token0Base => Orientation::Token1Base,
+            Orientation::Token1Base => Orientation::Token0Base,
+        }
+    }
+
+    /// Puts values given for token0 and token1 in (base, quote) order.
+    pub fn base_quote<T>(self, token0: T, token1: T) -> (T, T) {
+        match self {
+            Orientation::Token0Base => (token0, token1),
+            Orientation::Token1Base => (token1, token0),
+        }
+    }
+}
+
+/// A Uniswap pool together with the metadata of both of its tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pool {
+    pub address: Address,
+    pub version: ProtocolVersion,
+    pub token0: Token,
+    pub token1: Token,
+    /// Direction prices of this pool are reported in; token0 is the base
+    /// until the configured orientation is applied.
+    pub orientation: Orientation,
+}
+
+impl Pool {
+    /// Resolves token0/token1 of a pool and their ERC20 metadata, going
+    /// through `registry` for the tokens.
+    pub async fn fetch<P: Provider>(
+        provider: &P,
+        registry: &TokenRegistry,
+        chain_id: u64,
+        address: Address,
+        version: ProtocolVersion,
+    ) -> Result<Self> {
+        let mut pools =
+            Self::fetch_many(provider, registry, chain_id, &[(address, version)]).await?;
+        Ok(pools.remove(0))
+    }
+
+    /// Bootstraps many pools of one chain: one multicall for every pool's
+    /// token0/token1, then one for the metadata of tokens not yet cached.
+    pub async fn fetch_many<P: Provider>(
+        provider: &P,
+        registry: &TokenRegistry,
+        chain_id: u64,
+        pools: &[(Address, ProtocolVersion)],
+    ) -> Result<Vec<Self>> {
+        // token0()/token1() share their selectors between V2 pairs and V3 pools
+        let mut multicall = Multicall::new();
+        for &(address, _) in pools {
+            multicall.add(address, &UniswapV2Pair::token0Call {});
+            multicall.add(address, &UniswapV2Pair::token1Call {});
+        }
+
+        let results = multicall.call(provider, BlockId::latest()).await?;
+
+        let mut token_addresses = Vec::with_capacity(results.len());
+        for (&(address, _), outputs) in pools.iter().zip(results.chunks(2)) {
+            let token0 = decode::<UniswapV2Pair::token0Call>(&outputs[0]);
+            let token1 = decode::<UniswapV2Pair::token1Call>(&outputs[1]);
+            match (token0, token1) {
+                (Some(token0), Some(token1)) => token_addresses.extend([token0, token1]),
+                _ => bail!("{} is not a Uniswap pool", address),
+            }
+        }
+
+        let tokens = registry
+            .tokens(provider, chain_id, &token_addresses)
+            .await?;
+
+        Ok(pools
+            .iter()
+            .zip(tokens.chunks(2))
+            .map(|(&(address, version), tokens)| Self {
+                address,
+                version,
+                token0: tokens[0].clone(),
+                token1: tokens[1].clone(),
+                orientation: Orientation::default(),
+            })
+            .collect())
+    }
+
+    /// Pair label such as `WETH-USDC`, base first.
+    pub fn pair_name(&self) -> String {
+        format!("{}-{}", self.base().symbol, self.quote().symbol)
+    }
+
+    pub fn base(&self) -> &Token {
+        self.orientation.base_quote(&self.token0, &self.token1).0
+    }
+
+    pub fn quote(&self) -> &Token {
+        self.orientation.base_quote(&self.token0, &self.token1).1
+    }
+
+    /// Turns a token1-per-token0 price, as computed from reserves, swaps or
+    /// `sqrtPriceX96`, into quote per base.
+    pub fn orient_price(&self, token1_per_token0: Price) -> Price {
+        match self.orientation {
+            Orientation::Token0Base => token1_per_token0,
+            Orientation::Token1Base => token1_per_token0.inverse(),
+        }
+    }
+
+    /// A token1-per-token0 price labelled with the base and quote token.
+    pub fn price_quote(&self, token1_per_token0: Price) -> PriceQuote {
+        PriceQuote::new(
+            self.base().clone(),
+            self.quote().clone(),
+            self.orient_price(token1_per_token0),
+        )
+    }
+}
@@ -0,0 +1,264 @@
+use std::{cmp::Ordering, fmt};
+
+use alloy::primitives::{U160, U256};
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::token::Token;
+
+/// Digits after the decimal point used when a price is formatted without an
+/// explicit precision.
+pub const DEFAULT_PRICE_PRECISION: usize = 18;
+
+/// Exact, non-negative price kept as an unreduced fraction.
+///
+/// Formatting honours the precision of the format spec (`{:.10}`) and rounds
+/// half up at the last printed digit, so no precision is lost before output.
+#[derive(Debug, Clone)]
+pub struct Price {
+    numerator: BigUint,
+    denominator: BigUint,
+}
+
+impl Price {
+    pub fn zero() -> Self {
+        Self {
+            numerator: BigUint::zero(),
+            denominator: BigUint::from(1u8),
+        }
+    }
+
+    /// `numerator / denominator`, or zero when the denominator is zero.
+    pub fn from_ratio(numerator: U256, denominator: U256) -> Self {
+        if denominator.is_zero() {
+            return Self::zero();
+        }
+
+        Self {
+            numerator: to_biguint(numerator),
+            denominator: to_biguint(denominator),
+        }
+    }
+
+    /// Quote tokens paid per base token for raw amounts exchanged in a trade
+    /// or held as reserves, adjusted for both tokens' decimals.
+    pub fn from_amounts(
+        base_amount: U256,
+        base_decimals: u8,
+        quote_amount: U256,
+        quote_decimals: u8,
+    ) -> Self {
+        if base_amount.is_zero() || quote_amount.is_zero() {
+            return Self::zero();
+        }
+
+        Self {
+            numerator: to_biguint(quote_amount) * pow10(base_decimals),
+            denominator: to_biguint(base_amount) * pow10(quote_decimals),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    /// Reciprocal price; a zero price stays zero.
+    pub fn inverse(&self) -> Self {
+        if self.is_zero() {
+            return Self::zero();
+        }
+
+        Self {
+            numerator: self.denominator.clone(),
+            denominator: self.numerator.clone(),
+        }
+    }
+
+    /// Lossy conversion for plotting and statistics.
+    pub fn to_f64(&self) -> f64 {
+        let numerator = self.numerator.to_f64().unwrap_or(f64::INFINITY);
+        let denominator = self.denominator.to_f64().unwrap_or(f64::INFINITY);
+        numerator / denominator
+    }
+
+    /// Decimal representation with exactly `precision` fractional digits.
+    pub fn to_decimal_string(&self, precision: usize) -> String {
+        // round(n / d * 10^p) = (2 * n * 10^p + d) / (2 * d)
+        let scaled = (&self.numerator * BigUint::from(10u8).pow(precision as u32) * 2u8
+            + &self.denominator)
+            / (&self.denominator * 2u8);
+
+        let digits = format!("{:0>width$}", scaled.to_string(), width = precision + 1);
+        if precision == 0 {
+            return digits;
+        }
+
+        let (integer, fraction) = digits.split_at(digits.len() - precision);
+        format!("{}.{}", integer, fraction)
+    }
+}
+
+impl Default for Price {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.numerator * &other.denominator).cmp(&(&other.numerator * &self.denominator))
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(DEFAULT_PRICE_PRECISION);
+        f.pad_integral(true, "", &self.to_decimal_string(precision))
+    }
+}
+
+/// Price of one `base` token expressed in `quote` tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceQuote {
+    pub base: Token,
+    pub quote: Token,
+    pub price: Price,
+}
+
+impl PriceQuote {
+    pub fn new(base: Token, quote: Token, price: Price) -> Self {
+        Self { base, quote, price }
+    }
+
+    /// The same quote seen from the other side of the pair.
+    pub fn inverse(&self) -> Self {
+        Self {
+            base: self.quote.clone(),
+            quote: self.base.clone(),
+            price: self.price.inverse(),
+        }
+    }
+}
+
+impl fmt::Display for PriceQuote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(10);
+        write!(
+            f,
+            "1 {} = {:.precision$} {}",
+            self.base.symbol, self.price, self.quote.symbol
+        )
+    }
+}
+
+/// Token1 per token0 from Uniswap V2 reserves, adjusted for decimals.
+pub fn calculate_price_v2(
+    reserve0: U256,
+    reserve1: U256,
+    token0_decimals: u8,
+    token1_decimals: u8,
+) -> Price {
+    // In Uniswap V2, price = reserve1 / reserve0 (token1 per token0)
+    Price::from_amounts(reserve0, token0_decimals, reserve1, token1_decimals)
+}
+
+/// Token1 per token0 from a Uniswap V3 `sqrtPriceX96`, adjusted for decimals.
+pub fn calculate_price_v3(sqrt_price_x96: U160, token0_decimals: u8, token1_decimals: u8) -> Price {
+    let sqrt_price = to_biguint(U256::from(sqrt_price_x96));
+
+    // price = (sqrtPriceX96 / 2^96)^2 = sqrtPriceX96^2 / 2^192
+    Price {
+        numerator: &sqrt_price * &sqrt_price * pow10(token0_decimals),
+        denominator: (BigUint::from(1u8) << 192u32) * pow10(token1_decimals),
+    }
+}
+
+fn to_biguint(value: U256) -> BigUint {
+    BigUint::from_bytes_be(&value.to_be_bytes::<32>())
+}
+
+fn pow10(exponent: u8) -> BigUint {
+    BigUint::from(10u8).pow(exponent as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tick_math::{MAX_TICK, sqrt_price_at_tick};
+
+    fn ratio(numerator: u64, denominator: u64) -> Price {
+        Price::from_ratio(U256::from(numerator), U256::from(denominator))
+    }
+
+    #[test]
+    fn v3_price_beyond_u128_sqrt_price() {
+        // (2^130 / 2^96)^2 = 2^68
+        let sqrt_price = U160::from(1u8) << 130usize;
+        assert_eq!(
+            calculate_price_v3(sqrt_price, 0, 0).to_decimal_string(0),
+            "295147905179352825856"
+        );
+    }
+
+    #[test]
+    fn v3_price_at_max_sqrt_ratio() {
+        let sqrt_price = sqrt_price_at_tick(MAX_TICK).unwrap();
+        assert_eq!(
+            calculate_price_v3(sqrt_price, 0, 0).to_decimal_string(2),
+            "340256786836388094070642339899681172762.18"
+        );
+    }
+
+    #[test]
+    fn v2_price_with_mixed_decimals() {
+        // 2,500,000 USDC (6 decimals) against 1,000 WETH (18 decimals)
+        let usdc = U256::from(2_500_000u64) * U256::from(10u64).pow(U256::from(6u8));
+        let weth = U256::from(1_000u64) * U256::from(10u64).pow(U256::from(18u8));
+
+        let price = calculate_price_v2(usdc, weth, 6, 18);
+        assert_eq!(price.to_decimal_string(6), "0.000400");
+        assert_eq!(price.inverse().to_decimal_string(2), "2500.00");
+    }
+
+    #[test]
+    fn decimal_string_rounds_half_up() {
+        assert_eq!(ratio(1, 8).to_decimal_string(2), "0.13");
+        assert_eq!(ratio(1, 8).to_decimal_string(3), "0.125");
+        assert_eq!(ratio(5, 2).to_decimal_string(0), "3");
+        assert_eq!(ratio(1, 3).to_decimal_string(0), "0");
+        assert_eq!(ratio(2, 3).to_decimal_string(0), "1");
+        assert_eq!(ratio(2, 3).to_decimal_string(4), "0.6667");
+        assert_eq!(format!("{:.1}", ratio(199, 20)), "10.0");
+    }
+
+    #[test]
+    fn inverse_of_zero_is_zero() {
+        assert!(Price::zero().inverse().is_zero());
+        assert!(ratio(0, 5).inverse().is_zero());
+        assert!(ratio(5, 0).is_zero());
+        assert_eq!(Price::zero().inverse().to_decimal_string(2), "0.00");
+    }
+
+    #[test]
+    fn compares_unreduced_fractions() {
+        assert_eq!(ratio(1, 2), ratio(2, 4));
+        assert_eq!(ratio(3, 6).cmp(&ratio(50, 100)), Ordering::Equal);
+        assert!(ratio(1, 3) < ratio(2, 5));
+        assert!(ratio(7, 2) > ratio(10, 3));
+        assert_eq!(ratio(1, 3).max(ratio(2, 5)), ratio(4, 10));
+    }
+}
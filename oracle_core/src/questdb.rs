@@ -0,0 +1,201 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::B256;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use questdb::ingress::{Buffer, Sender, TimestampNanos};
+use tokio::task::JoinHandle;
+
+use crate::{config::QuestDbConfig, trade::Side};
+
+/// Rows buffered before a flush is forced.
+pub const MAX_BATCH_ROWS: usize = 1_000;
+
+/// Longest a row waits in the buffer before being sent.
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One swap as stored in the `trades` table.
+#[derive(Debug, Clone)]
+pub struct SwapRow {
+    /// Pair name such as `DAI-WETH`.
+    pub symbol: String,
+    pub side: Side,
+    /// Quote per base token.
+    pub price: f64,
+    /// Base tokens traded.
+    pub amount: f64,
+    pub timestamp: DateTime<Utc>,
+    /// Configured pool name.
+    pub pool: String,
+    pub block_number: u64,
+    pub tx_hash: Option<B256>,
+}
+
+/// Cloneable handle that queues rows for the QuestDB writer.
+///
+/// Writing never blocks the stream; the writer shuts down once every handle
+/// is dropped.
+#[derive(Debug, Clone)]
+pub struct SwapWriter {
+    rows: mpsc::Sender<SwapRow>,
+}
+
+impl SwapWriter {
+    /// Queues `row`; rows written after the sink failed are dropped, and the
+    /// failure is reported by [`QuestDbSink::finish`].
+    pub fn write(&self, row: SwapRow) {
+        let _ = self.rows.send(row);
+    }
+}
+
+/// Background ILP writer batching swaps into QuestDB.
+#[derive(Debug)]
+pub struct QuestDbSink {
+    worker: JoinHandle<Result<()>>,
+}
+
+impl QuestDbSink {
+    /// Connects to QuestDB and starts the writer.
+    ///
+    /// `config.ilp` is a client configuration string, e.g.
+    /// `tcp::addr=localhost:9009;`, so any TCP listener can stand in for a
+    /// real QuestDB.
+    pub async fn connect(config: &QuestDbConfig) -> Result<(SwapWriter, Self)> {
+        let ilp = config.ilp.clone();
+        let sender = tokio::task::spawn_blocking(move || Sender::from_conf(&ilp))
+            .await?
+            .with_context(|| format!("failed to connect to QuestDB at `{}`", config.ilp))?;
+
+        let (rows, receiver) = mpsc::channel();
+        let table = config.table.clone();
+        let worker = tokio::task::spawn_blocking(move || run(sender, &table, receiver));
+
+        Ok((SwapWriter { rows }, Self { worker }))
+    }
+
+    /// Resolves only if the writer stopped, which before shutdown means it
+    /// failed.
+    pub async fn stopped(&mut self) -> Result<()> {
+        (&mut self.worker)
+            .await
+            .context("QuestDB writer panicked")?
+    }
+
+    /// Waits for the final flush after every [`SwapWriter`] was dropped.
+    pub async fn finish(self) -> Result<()> {
+        self.worker.await.context("QuestDB writer panicked")?
+    }
+}
+
+fn run(mut sender: Sender, table: &str, rows: mpsc::Receiver<SwapRow>) -> Result<()> {
+    let mut buffer = sender.new_buffer();
+    let mut last_flush = Instant::now();
+
+    loop {
+        let timeout = FLUSH_INTERVAL.saturating_sub(last_flush.elapsed());
+        let closed = match rows.recv_timeout(timeout) {
+            Ok(row) => {
+                append(&mut buffer, table, &row)?;
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        if closed || buffer.row_count() >= MAX_BATCH_ROWS || last_flush.elapsed() >= FLUSH_INTERVAL
+        {
+            if !buffer.is_empty() {
+                sender
+                    .flush(&mut buffer)
+                    .context("failed to write swaps to QuestDB")?;
+            }
+            last_flush = Instant::now();
+        }
+
+        if closed {
+            return Ok(());
+        }
+    }
+}
+
+fn append(buffer: &mut Buffer, table: &str, row: &SwapRow) -> Result<()> {
+    buffer
+        .table(table)?
+        .symbol("symbol", &row.symbol)?
+        .symbol("side", row.side.as_str())?
+        .symbol("pool", &row.pool)?
+        .column_f64("price", row.price)?
+        .column_f64("amount", row.amount)?
+        .column_i64("block_number", row.block_number as i64)?;
+
+    if let Some(tx_hash) = row.tx_hash {
+        buffer.column_str("tx_hash", tx_hash.to_string())?;
+    }
+
+    buffer.at(TimestampNanos::from_datetime(row.timestamp)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, net::TcpListener, thread};
+
+    use super::*;
+
+    fn row(side: Side, block_number: u64, tx_hash: Option<B256>) -> SwapRow {
+        SwapRow {
+            symbol: "WETH-DAI".to_string(),
+            side,
+            price: 2500.5,
+            amount: 1.25,
+            timestamp: DateTime::from_timestamp(1_700_000_000, 123_000_000).unwrap(),
+            pool: "dai-weth".to_string(),
+            block_number,
+            tx_hash,
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_ilp_lines_to_a_tcp_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = String::new();
+            stream.read_to_string(&mut received).unwrap();
+            received
+        });
+
+        let config = QuestDbConfig {
+            ilp: format!("tcp::addr=127.0.0.1:{};", port),
+            table: "swaps".to_string(),
+            ..QuestDbConfig::default()
+        };
+        let (writer, sink) = QuestDbSink::connect(&config).await.unwrap();
+        let tx_hash = B256::repeat_byte(0xab);
+        writer.write(row(Side::Buy, 100, Some(tx_hash)));
+        writer.write(row(Side::Sell, 101, None));
+        drop(writer);
+        sink.finish().await.unwrap();
+
+        let lines: Vec<String> = received.join().unwrap().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert_eq!(
+            lines[0],
+            format!(
+                "swaps,symbol=WETH-DAI,side=buy,pool=dai-weth \
+                 price=2500.5,amount=1.25,block_number=100i,tx_hash=\"{}\" \
+                 1700000000123000000",
+                tx_hash
+            )
+        );
+        assert_eq!(
+            lines[1],
+            "swaps,symbol=WETH-DAI,side=sell,pool=dai-weth \
+             price=2500.5,amount=1.25,block_number=101i 1700000000123000000"
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use alloy::{primitives::Address, providers::Provider};
+use anyhow::{Context, Result};
+
+use crate::{cache_file::write_cache_file, token::Token};
+
+/// Token metadata cache keyed by chain id and token address.
+///
+/// Lookups that miss are resolved over RPC with [`Token::fetch_many`] and written
+/// back to the JSON store, so restarts and multi-pool runs only ever ask a
+/// node once per token. Tokens whose decimals were only assumed are cached
+/// for the current run but never written.
+#[derive(Debug)]
+pub struct TokenRegistry {
+    path: Option<PathBuf>,
+    tokens: Mutex<BTreeMap<String, Token>>,
+}
+
+impl TokenRegistry {
+    /// Opens the on-disk store at `path`, starting empty if it does not exist.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+
+        let tokens = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read token cache {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse token cache {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            tokens: Mutex::new(tokens),
+        })
+    }
+
+    /// A registry that is never persisted.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            tokens: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn get(&self, chain_id: u64, address: Address) -> Option<Token> {
+        let tokens = self.tokens.lock().unwrap();
+        tokens.get(&cache_key(chain_id, address)).cloned()
+    }
+
+    /// Cached metadata for `address`, fetched and stored on a miss.
+    pub async fn token<P: Provider>(
+        &self,
+        provider: &P,
+        chain_id: u64,
+        address: Address,
+    ) -> Result<Token> {
+        let mut tokens = self.tokens(provider, chain_id, &[address]).await?;
+        Ok(tokens.remove(0))
+    }
+
+    /// Cached metadata for every address, fetching all misses in one batch.
+    pub async fn tokens<P: Provider>(
+        &self,
+        provider: &P,
+        chain_id: u64,
+        addresses: &[Address],
+    ) -> Result<Vec<Token>> {
+        let mut missing: Vec<Address> = addresses
+            .iter()
+            .copied()
+            .filter(|&address| self.get(chain_id, address).is_none())
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        if !missing.is_empty() {
+            let fetched = Token::fetch_many(provider, &missing).await?;
+            self.insert(chain_id, fetched)?;
+        }
+
+        addresses
+            .iter()
+            .map(|&address| {
+                self.get(chain_id, address)
+                    .with_context(|| format!("token {} missing from registry", address))
+            })
+            .collect()
+    }
+
+    pub fn insert(&self, chain_id: u64, new_tokens: impl IntoIterator<Item = Token>) -> Result<()> {
+        let mut tokens = self.tokens.lock().unwrap();
+        for token in new_tokens {
+            tokens.insert(cache_key(chain_id, token.address), token);
+        }
+
+        match &self.path {
+            Some(path) => save(path, &tokens),
+            None => Ok(()),
+        }
+    }
+}
+
+fn cache_key(chain_id: u64, address: Address) -> String {
+    format!("{}:{}", chain_id, address)
+}
+
+fn save(path: &Path, tokens: &BTreeMap<String, Token>) -> Result<()> {
+    let tokens: BTreeMap<&String, &Token> = tokens
+        .iter()
+        .filter(|(_, token)| !token.assumed_decimals)
+        .collect();
+
+    write_cache_file(path, &serde_json::to_string_pretty(&tokens)?)
+        .with_context(|| format!("failed to write token cache {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_with_assumed_decimals_are_not_written() {
+        let dir = std::env::temp_dir().join(format!("token_registry_{}", std::process::id()));
+        let path = dir.join("tokens.json");
+        let _ = fs::remove_file(&path);
+
+        let known = Token::new(Address::repeat_byte(1), "DAI", 18);
+        let assumed = Token {
+            assumed_decimals: true,
+            ..Token::new(Address::repeat_byte(2), "ODD", 18)
+        };
+
+        let registry = TokenRegistry::open(&path).unwrap();
+        registry
+            .insert(1, [known.clone(), assumed.clone()])
+            .unwrap();
+        assert_eq!(registry.get(1, assumed.address), Some(assumed.clone()));
+
+        let reopened = TokenRegistry::open(&path).unwrap();
+        assert_eq!(reopened.get(1, known.address), Some(known));
+        assert_eq!(reopened.get(1, assumed.address), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use alloy::{
+    eips::BlockId,
+    primitives::{U160, U256},
+    providers::Provider,
+};
+use anyhow::Result;
+
+use crate::{
+    bindings::{UniswapV2Pair, UniswapV3Pool},
+    multicall::{Multicall, decode},
+    pool::{Pool, ProtocolVersion},
+    price::{Price, calculate_price_v2, calculate_price_v3},
+};
+
+/// Price-relevant state of a pool at one block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolState {
+    V2 {
+        reserve0: U256,
+        reserve1: U256,
+    },
+    V3 {
+        sqrt_price_x96: U160,
+        tick: i32,
+        observation_cardinality: u16,
+    },
+}
+
+impl PoolState {
+    /// Spot price of token0 in token1.
+    pub fn price(&self, pool: &Pool) -> Price {
+        match self {
+            PoolState::V2 { reserve0, reserve1 } => calculate_price_v2(
+                *reserve0,
+                *reserve1,
+                pool.token0.decimals,
+                pool.token1.decimals,
+            ),
+            PoolState::V3 { sqrt_price_x96, .. } => {
+                calculate_price_v3(*sqrt_price_x96, pool.token0.decimals, pool.token1.decimals)
+            }
+        }
+    }
+}
+
+/// Reads `getReserves()` / `slot0()` of every pool in one Multicall3 round
+/// trip. Pools whose call failed are `None`.
+pub async fn snapshot_pools<P: Provider>(
+    provider: &P,
+    pools: &[Pool],
+    block: BlockId,
+) -> Result<Vec<Option<PoolState>>> {
+    let mut multicall = Multicall::new();
+    for pool in pools {
+        match pool.version {
+            ProtocolVersion::V2 => multicall.add(pool.address, &UniswapV2Pair::getReservesCall {}),
+            ProtocolVersion::V3 => multicall.add(pool.address, &UniswapV3Pool::slot0Call {}),
+        };
+    }
+
+    let results = multicall.call(provider, block).await?;
+
+    Ok(pools
+        .iter()
+        .zip(&results)
+        .map(|(pool, output)| match pool.version {
+            ProtocolVersion::V2 => {
+                decode::<UniswapV2Pair::getReservesCall>(output).map(|reserves| PoolState::V2 {
+                    reserve0: U256::from(reserves.reserve0),
+                    reserve1: U256::from(reserves.reserve1),
+                })
+            }
+            ProtocolVersion::V3 => {
+                decode::<UniswapV3Pool::slot0Call>(output).map(|slot0| PoolState::V3 {
+                    sqrt_price_x96: slot0.sqrtPriceX96,
+                    tick: slot0.tick.as_i32(),
+                    observation_cardinality: slot0.observationCardinality,
+                })
+            }
+        })
+        .collect())
+}
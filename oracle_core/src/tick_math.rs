@@ -0,0 +1,99 @@
+use alloy::primitives::{U160, U256};
+use anyhow::{Result, bail};
+
+pub const MIN_TICK: i32 = -887272;
+pub const MAX_TICK: i32 = 887272;
+
+/// `sqrt(1.0001^-2^i) * 2^128` for each bit `i` of the absolute tick, as used
+/// by Uniswap V3's `TickMath`.
+const TICK_RATIOS: [u128; 19] = [
+    0xfff97272373d413259a46990580e213a,
+    0xfff2e50f5f656932ef12357cf3c7fdcc,
+    0xffe5caca7e10e4e61c3624eaa0941cd0,
+    0xffcb9843d60f6159c9db58835c926644,
+    0xff973b41fa98c081472e6896dfb254c0,
+    0xff2ea16466c96a3843ec78b326b52861,
+    0xfe5dee046a99a2a811c461f1969c3053,
+    0xfcbe86c7900a88aedcffc83b479aa3a4,
+    0xf987a7253ac413176f2b074cf7815e54,
+    0xf3392b0822b70005940c7a398e4b70f3,
+    0xe7159475a2c29b7443b29c7fa6e889d9,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e5,
+    0x70d869a156d2a1b890bb3df62baf32f7,
+    0x31be135f97d08fd981231505542fcfa6,
+    0x9aa508b5b7a84e1c677de54f3e99bc9,
+    0x5d6af8dedb81196699c329225ee604,
+    0x2216e584f5fa1ea926041bedfe98,
+    0x48a170391f7dc42444e8fa2,
+];
+
+/// `sqrt(1.0001^tick) * 2^96`, bit-for-bit identical to
+/// `TickMath.getSqrtRatioAtTick`.
+pub fn sqrt_price_at_tick(tick: i32) -> Result<U160> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        bail!("tick {} is outside [{}, {}]", tick, MIN_TICK, MAX_TICK);
+    }
+
+    let abs_tick = tick.unsigned_abs();
+
+    let mut ratio = if abs_tick & 0x1 != 0 {
+        U256::from(0xfffcb933bd6fad37aa2d162d1a594001u128)
+    } else {
+        U256::from(1) << 128
+    };
+
+    for (bit, &factor) in TICK_RATIOS.iter().enumerate() {
+        if abs_tick & (0x2 << bit) != 0 {
+            ratio = (ratio * U256::from(factor)) >> 128;
+        }
+    }
+
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Round up when dividing by 2^32 so the result is never below the true value
+    let round_up = ratio & U256::from(u32::MAX) != U256::ZERO;
+    let sqrt_price = (ratio >> 32) + U256::from(round_up as u8);
+
+    Ok(U160::from(sqrt_price))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqrt_price(tick: i32) -> String {
+        sqrt_price_at_tick(tick).unwrap().to_string()
+    }
+
+    #[test]
+    fn matches_tick_math_at_the_bounds() {
+        assert_eq!(sqrt_price(MIN_TICK), "4295128739");
+        assert_eq!(sqrt_price(MIN_TICK + 1), "4295343490");
+        assert_eq!(
+            sqrt_price(MAX_TICK - 1),
+            "1461373636630004318706518188784493106690254656249"
+        );
+        assert_eq!(
+            sqrt_price(MAX_TICK),
+            "1461446703485210103287273052203988822378723970342"
+        );
+    }
+
+    #[test]
+    fn matches_tick_math_inside_the_range() {
+        assert_eq!(sqrt_price(0), (U256::from(1u8) << 96usize).to_string());
+        assert_eq!(sqrt_price(-50), "79030349367926598376800521322");
+        assert_eq!(sqrt_price(50), "79426470787362580746886972461");
+        assert_eq!(sqrt_price(-100_000), "533968626430936354154228408");
+        assert_eq!(sqrt_price(100_000), "11755562826496067164730007768450");
+    }
+
+    #[test]
+    fn rejects_ticks_out_of_range() {
+        assert!(sqrt_price_at_tick(MIN_TICK - 1).is_err());
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    eips::BlockNumberOrTag, primitives::U64, providers::Provider, rpc::client::BatchRequest,
+};
+use anyhow::{Context, Result};
+use futures_util::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+
+use crate::cache_file::write_cache_file;
+
+/// Blocks requested per JSON-RPC batch.
+pub const BLOCKS_PER_BATCH: usize = 100;
+
+/// Batches in flight at once.
+const CONCURRENT_BATCHES: usize = 4;
+
+/// Shortest time between two writes of the cache file while new blocks keep
+/// coming in.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The only part of `eth_getBlockByNumber` we need.
+#[derive(Debug, Deserialize)]
+struct BlockHeader {
+    timestamp: U64,
+}
+
+/// Block number to timestamp cache for one chain.
+///
+/// Unknown blocks are fetched with batched `eth_getBlockByNumber` requests
+/// and, when opened from disk, persisted so later backfills skip them. The
+/// file is rewritten at most once per [`SAVE_INTERVAL`], and once more on
+/// [`save`](Self::save) or drop, so following the chain does not rewrite it
+/// for every block.
+#[derive(Debug)]
+pub struct BlockTimestamps {
+    path: Option<PathBuf>,
+    cache: Mutex<Cache>,
+}
+
+#[derive(Debug)]
+struct Cache {
+    timestamps: BTreeMap<u64, u64>,
+    /// Whether `timestamps` changed since the file was written
+    dirty: bool,
+    last_save: Instant,
+}
+
+impl Cache {
+    fn new(timestamps: BTreeMap<u64, u64>) -> Self {
+        Self {
+            timestamps,
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+}
+
+impl BlockTimestamps {
+    /// Opens `<dir>/<chain_id>.json`, starting empty if it does not exist.
+    pub fn open(dir: &Path, chain_id: u64) -> Result<Self> {
+        let path = dir.join(format!("{}.json", chain_id));
+
+        let timestamps = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read timestamp cache {}", path.display()))?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse timestamp cache {}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            cache: Mutex::new(Cache::new(timestamps)),
+        })
+    }
+
+    /// A cache that is never persisted.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            cache: Mutex::new(Cache::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn get(&self, block_number: u64) -> Option<u64> {
+        self.cache
+            .lock()
+            .unwrap()
+            .timestamps
+            .get(&block_number)
+            .copied()
+    }
+
+    /// Records timestamps already known to the caller, e.g. from
+    /// `log.block_timestamp`.
+    pub fn record(&self, block_number: u64, timestamp: u64) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.timestamps.insert(block_number, timestamp) != Some(timestamp) {
+            cache.dirty = true;
+        }
+    }
+
+    /// Writes the cache file if anything changed since it was last written.
+    pub fn save(&self) -> Result<()> {
+        let mut cache = self.cache.lock().unwrap();
+        self.save_locked(&mut cache)
+    }
+
+    fn save_locked(&self, cache: &mut Cache) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if cache.dirty {
+            save(path, &cache.timestamps)?;
+            cache.dirty = false;
+        }
+        cache.last_save = Instant::now();
+        Ok(())
+    }
+
+    /// Timestamps of every block, fetching each unknown block once.
+    pub async fn resolve<P: Provider>(
+        &self,
+        provider: &P,
+        block_numbers: impl IntoIterator<Item = u64>,
+    ) -> Result<HashMap<u64, u64>> {
+        let wanted: BTreeSet<u64> = block_numbers.into_iter().collect();
+        let missing: Vec<u64> = wanted
+            .iter()
+            .copied()
+            .filter(|&block_number| self.get(block_number).is_none())
+            .collect();
+
+        if !missing.is_empty() {
+            let fetched: Vec<Vec<(u64, u64)>> = stream::iter(missing.chunks(BLOCKS_PER_BATCH))
+                .map(|chunk| fetch_batch(provider, chunk))
+                .buffer_unordered(CONCURRENT_BATCHES)
+                .try_collect()
+                .await?;
+
+            let mut cache = self.cache.lock().unwrap();
+            cache.timestamps.extend(fetched.into_iter().flatten());
+            cache.dirty = true;
+            if cache.last_save.elapsed() >= SAVE_INTERVAL {
+                self.save_locked(&mut cache)?;
+            }
+        }
+
+        let cache = self.cache.lock().unwrap();
+        Ok(wanted
+            .into_iter()
+            .filter_map(|block_number| {
+                cache
+                    .timestamps
+                    .get(&block_number)
+                    .map(|&timestamp| (block_number, timestamp))
+            })
+            .collect())
+    }
+}
+
+impl Drop for BlockTimestamps {
+    /// Last chance to persist what was fetched; it is only a cache, so a
+    /// failed write is not worth a panic.
+    fn drop(&mut self) {
+        let _ = self.save();
+    }
+}
+
+/// One JSON-RPC batch of `eth_getBlockByNumber` calls without transactions.
+async fn fetch_batch<P: Provider>(provider: &P, block_numbers: &[u64]) -> Result<Vec<(u64, u64)>> {
+    let client = provider.client();
+    let mut batch = BatchRequest::new(client);
+
+    let mut waiters = Vec::with_capacity(block_numbers.len());
+    for &block_number in block_numbers {
+        let params = (BlockNumberOrTag::Number(block_number), false);
+        waiters.push(batch.add_call::<_, Option<BlockHeader>>("eth_getBlockByNumber", &params)?);
+    }
+
+    batch.send().await?;
+
+    let mut timestamps = Vec::with_capacity(block_numbers.len());
+    for (&block_number, waiter) in block_numbers.iter().zip(waiters) {
+        let header = waiter
+            .await?
+            .with_context(|| format!("block {} not found", block_number))?;
+        timestamps.push((block_number, header.timestamp.to::<u64>()));
+    }
+
+    Ok(timestamps)
+}
+
+fn save(path: &Path, timestamps: &BTreeMap<u64, u64>) -> Result<()> {
+    write_cache_file(path, &serde_json::to_string(timestamps)?)
+        .with_context(|| format!("failed to write timestamp cache {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_the_file_on_save_and_drop_only() {
+        let dir = std::env::temp_dir().join(format!("block_timestamps_{}", std::process::id()));
+        let path = dir.join("1.json");
+        let _ = fs::remove_file(&path);
+
+        let timestamps = BlockTimestamps::open(&dir, 1).unwrap();
+        timestamps.record(100, 1_700_000_000);
+        assert!(!path.exists());
+        timestamps.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"100":1700000000}"#);
+
+        timestamps.record(101, 1_700_000_012);
+        drop(timestamps);
+        let reopened = BlockTimestamps::open(&dir, 1).unwrap();
+        assert_eq!(reopened.get(101), Some(1_700_000_012));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
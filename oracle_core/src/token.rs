@@ -0,0 +1,123 @@
+use alloy::{eips::BlockId, primitives::Address, providers::Provider, sol_types::SolCall};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bindings::ERC20,
+    multicall::{Multicall, decode},
+};
+
+/// Decimals assumed for tokens that do not implement the optional `decimals()`.
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+/// ERC20 metadata needed to scale raw amounts and label prices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    /// `decimals()` failed and [`DEFAULT_DECIMALS`] was assumed. Such tokens
+    /// are kept out of the on-disk registry so a later run asks again.
+    #[serde(skip)]
+    pub assumed_decimals: bool,
+}
+
+impl Token {
+    pub fn new(address: Address, symbol: impl Into<String>, decimals: u8) -> Self {
+        Self {
+            address,
+            symbol: symbol.into(),
+            decimals,
+            assumed_decimals: false,
+        }
+    }
+
+    /// Reads `symbol()` and `decimals()` from the token contract.
+    pub async fn fetch<P: Provider>(provider: &P, address: Address) -> Result<Self> {
+        let mut tokens = Self::fetch_many(provider, &[address]).await?;
+        Ok(tokens.remove(0))
+    }
+
+    /// Reads the metadata of every token in one Multicall3 round trip.
+    ///
+    /// Symbols returned as `bytes32` (e.g. MKR) are accepted, and tokens
+    /// without a working `decimals()` fall back to [`DEFAULT_DECIMALS`] with a
+    /// warning.
+    pub async fn fetch_many<P: Provider>(provider: &P, addresses: &[Address]) -> Result<Vec<Self>> {
+        let mut multicall = Multicall::new();
+        for &address in addresses {
+            multicall.add(address, &ERC20::symbolCall {});
+            multicall.add(address, &ERC20::decimalsCall {});
+        }
+
+        let results = multicall.call(provider, BlockId::latest()).await?;
+
+        addresses
+            .iter()
+            .zip(results.chunks(2))
+            .map(|(&address, outputs)| {
+                let symbol = match &outputs[0] {
+                    Some(output) => decode_symbol(output)?,
+                    None => bail!("token {} does not implement symbol()", address),
+                };
+                let Some(decimals) = decode::<ERC20::decimalsCall>(&outputs[1]) else {
+                    eprintln!(
+                        "token {} ({}): decimals() failed, assuming {}",
+                        symbol, address, DEFAULT_DECIMALS
+                    );
+                    return Ok(Self {
+                        assumed_decimals: true,
+                        ..Self::new(address, symbol, DEFAULT_DECIMALS)
+                    });
+                };
+
+                Ok(Self::new(address, symbol, decimals))
+            })
+            .collect()
+    }
+}
+
+/// Decodes a `symbol()` result encoded either as `string` or as `bytes32`.
+fn decode_symbol(output: &[u8]) -> Result<String> {
+    if let Ok(symbol) = ERC20::symbolCall::abi_decode_returns(output) {
+        return Ok(symbol);
+    }
+
+    if output.len() == 32 {
+        let end = output.iter().position(|&b| b == 0).unwrap_or(32);
+        return Ok(String::from_utf8_lossy(&output[..end]).into_owned());
+    }
+
+    bail!("unrecognised symbol() return data: {} bytes", output.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::sol_types::SolValue;
+
+    use super::*;
+
+    #[test]
+    fn decodes_string_symbol() {
+        let output = "WETH".to_string().abi_encode();
+        assert_eq!(decode_symbol(&output).unwrap(), "WETH");
+    }
+
+    #[test]
+    fn decodes_bytes32_symbol() {
+        // MKR returns its symbol as a zero-padded bytes32
+        let mut output = [0u8; 32];
+        output[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_symbol(&output).unwrap(), "MKR");
+
+        let full = [b'A'; 32];
+        assert_eq!(decode_symbol(&full).unwrap(), "A".repeat(32));
+    }
+
+    #[test]
+    fn rejects_other_lengths() {
+        assert!(decode_symbol(&[]).is_err());
+        assert!(decode_symbol(b"MKR").is_err());
+        assert!(decode_symbol(&[0xff; 40]).is_err());
+    }
+}
@@ -0,0 +1,32 @@
+use std::fmt;
+
+use crate::amount::AmountStyle;
+
+/// How the streamers print the token amounts of a trade.
+pub const TRADE_AMOUNT_STYLE: AmountStyle = AmountStyle::Significant(8);
+
+/// Which way a swap moved the pool's base token: a buy takes it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// Lowercase name, as stored in the `side` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Buy => write!(f, "BUY"),
+            Side::Sell => write!(f, "SELL"),
+        }
+    }
+}
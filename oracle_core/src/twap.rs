@@ -0,0 +1,148 @@
+use alloy::{eips::BlockNumberOrTag, primitives::U256, providers::Provider};
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    bindings::UniswapV3Pool,
+    pool::{Pool, ProtocolVersion},
+    price::{Price, calculate_price_v3},
+    tick_math::sqrt_price_at_tick,
+};
+
+/// Time-weighted average over the last `window` seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Twap {
+    pub window: u32,
+    /// Arithmetic mean tick, rounded toward negative infinity like
+    /// Uniswap's `OracleLibrary.consult`.
+    pub tick: i32,
+    /// Token1 per token0 at the mean tick.
+    pub price: Price,
+}
+
+/// Reads the V3 oracle with `observe()` and returns one TWAP per window.
+///
+/// Fails before calling `observe()` when the pool's observation buffer does
+/// not reach back far enough for the longest window.
+pub async fn observe_twap<P: Provider>(
+    provider: &P,
+    pool: &Pool,
+    windows: &[u32],
+) -> Result<Vec<Twap>> {
+    if pool.version != ProtocolVersion::V3 {
+        bail!(
+            "TWAP requires a V3 pool, {} is {:?}",
+            pool.address,
+            pool.version
+        );
+    }
+    if windows.contains(&0) {
+        bail!("TWAP windows must be longer than zero seconds");
+    }
+
+    let Some(&longest) = windows.iter().max() else {
+        return Ok(Vec::new());
+    };
+
+    let history = available_history(provider, pool).await?;
+    if longest > history.seconds {
+        bail!(
+            "pool {} only keeps {}s of price history (observationCardinality = {}), \
+             which is too short for a {}s window; raise it with increaseObservationCardinalityNext",
+            pool.address,
+            history.seconds,
+            history.cardinality,
+            longest
+        );
+    }
+
+    let pool_contract = UniswapV3Pool::new(pool.address, provider);
+
+    // One observe() call for every window plus "now"
+    let seconds_agos: Vec<u32> = windows.iter().copied().chain([0]).collect();
+    let observations = pool_contract
+        .observe(seconds_agos)
+        .call()
+        .await
+        .with_context(|| format!("observe() failed for pool {}", pool.address))?;
+
+    let cumulatives = &observations.tickCumulatives;
+    let now = cumulatives[windows.len()].as_i64();
+
+    windows
+        .iter()
+        .zip(cumulatives)
+        .map(|(&window, cumulative)| {
+            let tick = mean_tick(now - cumulative.as_i64(), window);
+
+            let sqrt_price_x96 = sqrt_price_at_tick(tick)?;
+            let price =
+                calculate_price_v3(sqrt_price_x96, pool.token0.decimals, pool.token1.decimals);
+
+            Ok(Twap {
+                window,
+                tick,
+                price,
+            })
+        })
+        .collect()
+}
+
+/// Mean tick over `window` seconds from the tick cumulative delta, rounded
+/// towards negative infinity like `OracleLibrary.consult`.
+fn mean_tick(delta: i64, window: u32) -> i32 {
+    let mut tick = delta / window as i64;
+    if delta < 0 && delta % window as i64 != 0 {
+        tick -= 1;
+    }
+    tick as i32
+}
+
+struct History {
+    seconds: u32,
+    cardinality: u16,
+}
+
+/// How far back the pool's observation ring buffer currently reaches.
+async fn available_history<P: Provider>(provider: &P, pool: &Pool) -> Result<History> {
+    let pool_contract = UniswapV3Pool::new(pool.address, provider);
+
+    let slot0 = pool_contract.slot0().call().await?;
+    let cardinality = slot0.observationCardinality;
+
+    // The slot after the latest one is the oldest, unless the buffer has not
+    // wrapped around yet, in which case slot 0 is
+    let next_index = (slot0.observationIndex as u64 + 1) % cardinality.max(1) as u64;
+    let mut oldest = pool_contract
+        .observations(U256::from(next_index))
+        .call()
+        .await?;
+    if !oldest.initialized {
+        oldest = pool_contract.observations(U256::ZERO).call().await?;
+    }
+
+    let latest_block = provider
+        .get_block_by_number(BlockNumberOrTag::Latest)
+        .await?
+        .context("latest block not found")?;
+    let seconds = (latest_block.header.timestamp as u32).saturating_sub(oldest.blockTimestamp);
+
+    Ok(History {
+        seconds,
+        cardinality,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_tick_rounds_down() {
+        assert_eq!(mean_tick(600, 60), 10);
+        assert_eq!(mean_tick(650, 60), 10);
+        assert_eq!(mean_tick(-600, 60), -10);
+        assert_eq!(mean_tick(-601, 60), -11);
+        assert_eq!(mean_tick(-1, 60), -1);
+        assert_eq!(mean_tick(0, 60), 0);
+    }
+}
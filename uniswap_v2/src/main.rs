@@ -1,95 +1,169 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
 use alloy::{
-    primitives::{Address, U256, address},
-    providers::{Provider, ProviderBuilder, WsConnect},
+    eips::BlockId,
+    primitives::Address,
+    providers::{ProviderBuilder, WsConnect},
     rpc::types::{BlockNumberOrTag, Filter},
-    sol,
     sol_types::SolEvent,
 };
 use anyhow::Result;
-use chrono::DateTime;
-use futures_util::StreamExt;
-
-const RPC_URL: &str = "wss://arbitrum-one-rpc.publicnode.com";
-
-const ADDRESS: Address = address!("0xf64dfe17c8b87f012fcf50fbda1d62bfa148366a");
-
-sol! {
-    #[sol(rpc)]
-    contract UniswapV2Pair {
-        // Sync event - reserve changes after each swap
-        event Sync(uint112 reserve0, uint112 reserve1);
-
-        // Swap event - for volume calculation
-        event Swap(
-            address indexed sender,
-            uint amount0In,
-            uint amount1In,
-            uint amount0Out,
-            uint amount1Out,
-            address indexed to
-        );
+use clap::Parser;
+use futures_util::future::try_join_all;
+use oracle_core::{
+    ChainConfig, ConfigArgs, Finality, LogUpdate, Pool, PoolConfig, ProtocolVersion, QuestDbSink,
+    SwapWriter, TokenRegistry, UniswapV2Pair, follow_logs, snapshot_pools,
+};
 
-        function token0() external view returns (address);
-        function token1() external view returns (address);
-        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
-    }
+mod trade;
 
-    #[sol(rpc)]
-    contract ERC20 {
-        function decimals() external view returns (uint8);
-        function symbol() external view returns (string);
-    }
+use trade::{Retraction, Trade};
+
+/// A streamed pair. Swaps are printed in `pool`'s orientation but stored in
+/// `stored`'s, the configured one, so `--invert` never changes what QuestDB
+/// holds.
+struct Pair {
+    name: String,
+    pool: Pool,
+    stored: Pool,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // let provider = ProviderBuilder::new().connect(RPC_URL).await?;
-    let ws = WsConnect::new(RPC_URL);
-    let provider = ProviderBuilder::new().connect_ws(ws).await?;
+/// Stream swaps of configured Uniswap V2 pairs.
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
 
-    let pair_contract = UniswapV2Pair::new(ADDRESS, &provider);
+    #[command(flatten)]
+    finality: Finality,
+}
 
-    let token0_addr = pair_contract.token0().call().await?;
-    let token1_addr = pair_contract.token1().call().await?;
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let config = cli.config.load()?;
+    let registry = Arc::new(config.token_registry()?);
+
+    // One subscription per chain, each covering every selected pair on it
+    let mut pools_by_chain: BTreeMap<&str, Vec<PoolConfig>> = BTreeMap::new();
+    for pool_config in cli.config.select_pools(&config, ProtocolVersion::V2)? {
+        pools_by_chain
+            .entry(&pool_config.chain)
+            .or_default()
+            .push(pool_config.clone());
+    }
 
-    let token0_contract = ERC20::new(token0_addr, &provider);
-    let token1_contract = ERC20::new(token1_addr, &provider);
+    let (writer, mut sink) = match &config.questdb {
+        Some(questdb) => {
+            let (writer, sink) = QuestDbSink::connect(questdb).await?;
+            (Some(writer), Some(sink))
+        }
+        None => (None, None),
+    };
+
+    let streams = try_join_all(
+        pools_by_chain
+            .into_iter()
+            .map(|(chain_name, pool_configs)| {
+                let chain = config.chains[chain_name].clone();
+                stream_swaps(
+                    &cli.config,
+                    chain,
+                    pool_configs,
+                    registry.clone(),
+                    cli.finality,
+                    writer.clone(),
+                )
+            }),
+    );
+    // Only the streams hold writers now, so the sink shuts down with them
+    drop(writer);
+
+    let sink_stopped = async {
+        match &mut sink {
+            Some(sink) => sink.stopped().await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        result = streams => {
+            result?;
+        }
+        result = sink_stopped => return result,
+        _ = tokio::signal::ctrl_c() => eprintln!("Interrupted, flushing pending swaps"),
+    }
 
-    let token0_decimals = token0_contract.decimals().call().await?;
-    let token1_decimals = token1_contract.decimals().call().await?;
+    if let Some(sink) = sink {
+        sink.finish().await?;
+    }
 
-    let token0_symbol = token0_contract.symbol().call().await?;
-    let token1_symbol = token1_contract.symbol().call().await?;
+    Ok(())
+}
 
-    println!("Token0: {} ({})", token0_symbol, token0_addr);
-    println!("Token1: {} ({})", token1_symbol, token1_addr);
+async fn stream_swaps(
+    config_args: &ConfigArgs,
+    chain: ChainConfig,
+    pool_configs: Vec<PoolConfig>,
+    registry: Arc<TokenRegistry>,
+    finality: Finality,
+    writer: Option<SwapWriter>,
+) -> Result<()> {
+    // let provider = ProviderBuilder::new().connect(&chain.rpc_url).await?;
+    let ws = WsConnect::new(chain.ws_url());
+    let provider = ProviderBuilder::new().connect_ws(ws).await?;
 
-    let reserves = pair_contract.getReserves().call().await?;
-    let reserve0 = reserves.reserve0;
-    let reserve1 = reserves.reserve1;
+    // Resolve token metadata and current reserves once per pair, batched
+    // through Multicall3
+    let addresses: Vec<_> = pool_configs
+        .iter()
+        .map(|pool_config| (pool_config.address, ProtocolVersion::V2))
+        .collect();
+    let fetched = Pool::fetch_many(&provider, &registry, chain.chain_id, &addresses).await?;
+    let states = snapshot_pools(&provider, &fetched, BlockId::latest()).await?;
+
+    let mut pools: HashMap<Address, Pair> = HashMap::new();
+    for ((pool_config, mut pool), state) in pool_configs.iter().zip(fetched).zip(states) {
+        let stored = Pool {
+            orientation: pool_config.orientation(&pool)?,
+            ..pool.clone()
+        };
+        config_args.orient(pool_config, &mut pool)?;
+
+        println!(
+            "[{}] Token0: {} ({})",
+            pool_config.name, pool.token0.symbol, pool.token0.address
+        );
+        println!(
+            "[{}] Token1: {} ({})",
+            pool_config.name, pool.token1.symbol, pool.token1.address
+        );
 
-    let token0_per_token1 = calculate_price_v2(
-        reserve0.to(),
-        reserve1.to(),
-        token0_decimals,
-        token1_decimals,
-    );
+        if let Some(state) = state {
+            let quote = pool.price_quote(state.price(&pool));
 
-    println!(
-        "1 {} = {:.10} {}",
-        token0_symbol, token0_per_token1, token1_symbol
-    );
+            println!("[{}] {}", pool_config.name, quote);
+            println!("[{}] {}", pool_config.name, quote.inverse());
+        }
 
-    let token1_per_token0 = 1.0 / token0_per_token1;
+        pools.insert(
+            pool_config.address,
+            Pair {
+                name: pool_config.name.clone(),
+                pool,
+                stored,
+            },
+        );
+    }
 
-    println!(
-        "1 {} = {:.10} {}",
-        token1_symbol, token1_per_token0, token0_symbol
-    );
+    let addresses: Vec<Address> = pools.keys().copied().collect();
 
     /*
     let filter = Filter::new()
-        .address(ADDRESS)
+        .address(addresses.clone())
         .event(UniswapV2Pair::Sync::SIGNATURE)
         .from_block(BlockNumberOrTag::Latest);
 
@@ -106,128 +180,45 @@ async fn main() -> Result<()> {
     */
 
     let filter = Filter::new()
-        .address(ADDRESS)
+        .address(addresses)
         .event(UniswapV2Pair::Swap::SIGNATURE)
         .from_block(BlockNumberOrTag::Latest);
 
-    let sub = provider.subscribe_logs(&filter).await?;
-    let mut stream = sub.into_stream();
-
-    while let Some(log) = stream.next().await {
-        let token0_symbol_clone = token0_symbol.clone();
-        let token1_symbol_clone = token1_symbol.clone();
-
-        let pair = format!("{}-{}", token0_symbol_clone, token1_symbol_clone);
-
-        let block_timestamp = log
-            .block_timestamp
-            .unwrap_or(chrono::Utc::now().timestamp() as u64);
-
-        let timestamp = DateTime::from_timestamp(block_timestamp as i64, 0).unwrap();
-
-        tokio::spawn(async move {
-            let swap = UniswapV2Pair::Swap::decode_log_data(log.data()).unwrap();
-
-            // Determine trade direction based on ETH perspective
-            let (trade_type, amount_in, amount_out, in_symbol, out_symbol) =
-                if swap.amount0In > U256::ZERO && swap.amount1Out > U256::ZERO {
-                    // Sending token0 to get token1
-                    if token0_symbol_clone == "WETH" {
-                        // Selling ETH for USDC
-                        (
-                            "SELL",
-                            format_token_amount(swap.amount0In, token0_decimals),
-                            format_token_amount(swap.amount1Out, token1_decimals),
-                            token0_symbol_clone.clone(),
-                            token1_symbol_clone.clone(),
-                        )
-                    } else {
-                        // Buying ETH with USDC
-                        (
-                            "BUY",
-                            format_token_amount(swap.amount0In, token0_decimals),
-                            format_token_amount(swap.amount1Out, token1_decimals),
-                            token0_symbol_clone.clone(),
-                            token1_symbol_clone.clone(),
-                        )
-                    }
-                } else if swap.amount1In > U256::ZERO && swap.amount0Out > U256::ZERO {
-                    // Sending token1 to get token0
-                    if token1_symbol_clone == "WETH" {
-                        // Selling ETH for USDC
-                        (
-                            "SELL",
-                            format_token_amount(swap.amount1In, token1_decimals),
-                            format_token_amount(swap.amount0Out, token0_decimals),
-                            token1_symbol_clone.clone(),
-                            token0_symbol_clone.clone(),
-                        )
-                    } else {
-                        // Buying ETH with USDC
-                        (
-                            "BUY",
-                            format_token_amount(swap.amount1In, token1_decimals),
-                            format_token_amount(swap.amount0Out, token0_decimals),
-                            token1_symbol_clone.clone(),
-                            token0_symbol_clone.clone(),
-                        )
-                    }
-                } else {
-                    return; // Invalid swap
-                };
-
-            println!(
-                "{} - {}: {} | {} {} → {} {}",
-                timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
-                pair,
-                trade_type,
-                amount_in,
-                in_symbol,
-                amount_out,
-                out_symbol,
-            );
-        });
-    }
-
-    Ok(())
+    follow_logs(&chain, &filter, finality, |updates| {
+        print_updates(&pools, writer.as_ref(), updates)
+    })
+    .await
 }
 
-// Format token amount with decimals
-fn format_token_amount(amount: U256, decimals: u8) -> String {
-    let divisor = 10_f64.powi(decimals as i32);
-    let amount_f64 = amount.to::<u128>() as f64 / divisor;
-
-    // Format with appropriate precision
-    if amount_f64 >= 1.0 {
-        format!("{:.4}", amount_f64)
-    } else if amount_f64 >= 0.0001 {
-        format!("{:.8}", amount_f64)
-    } else {
-        format!("{:.12}", amount_f64)
+fn print_updates(
+    pools: &HashMap<Address, Pair>,
+    writer: Option<&SwapWriter>,
+    updates: Vec<LogUpdate>,
+) {
+    for update in updates {
+        let (LogUpdate::Emit(log) | LogUpdate::Retract(log)) = &update;
+        let Some(pair) = pools.get(&log.address()) else {
+            continue;
+        };
+        let Some(trade) = Trade::from_log(&pair.name, &pair.pool, log) else {
+            continue;
+        };
+
+        match update {
+            LogUpdate::Emit(_) => {
+                println!("{}", trade);
+                let stored = writer.zip(Trade::from_log(&pair.name, &pair.stored, log));
+                if let Some((writer, stored)) = stored {
+                    writer.write(stored.to_swap_row(&pair.stored));
+                }
+            }
+            // ILP is append-only, so a retracted swap stays in QuestDB;
+            // --confirmations keeps reorged swaps from being written at all
+            LogUpdate::Retract(_) => println!("{}", Retraction(&trade)),
+        }
     }
 }
 
-fn calculate_price_v2(
-    reserve0: u128,
-    reserve1: u128,
-    token0_decimals: u8,
-    token1_decimals: u8,
-) -> f64 {
-    // In Uniswap V2, price = reserve1 / reserve0 (token1 per token0)
-    // But we want token0 per token1, so we need reserve0 / reserve1
-    let reserve0_f64 = reserve0 as f64;
-    let reserve1_f64 = reserve1 as f64;
-
-    // Calculate the raw price ratio
-    let price_ratio = reserve1_f64 / reserve0_f64;
-
-    // Adjust for decimal differences
-    let price_adjusted =
-        price_ratio * 10_f64.powi((token0_decimals as i32) - (token1_decimals as i32));
-
-    price_adjusted
-}
-
 /*
 SELECT
     timestamp,
@@ -0,0 +1,130 @@
+use std::fmt;
+
+use alloy::{
+    primitives::{B256, U256},
+    rpc::types::Log,
+    sol_types::SolEvent,
+};
+use chrono::{DateTime, Utc};
+use oracle_core::{
+    Pool, Price, Side, SwapRow, TRADE_AMOUNT_STYLE, Token, UniswapV2Pair, format_token_amount,
+    token_amount_to_f64,
+};
+
+/// A decoded `Swap` log tagged with the pool it came from.
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub pool_name: String,
+    pub pair: String,
+    pub timestamp: DateTime<Utc>,
+    pub side: Side,
+    pub token_in: Token,
+    pub token_out: Token,
+    pub amount_in: U256,
+    pub amount_out: U256,
+    /// Base token amount that changed hands.
+    pub base_amount: U256,
+    /// Execution price, quote per base.
+    pub price: Price,
+    pub block_number: u64,
+    pub tx_hash: Option<B256>,
+}
+
+impl Trade {
+    /// Decodes a V2 `Swap` log of `pool`; `None` if it is not a valid swap.
+    pub fn from_log(pool_name: &str, pool: &Pool, log: &Log) -> Option<Self> {
+        let swap = UniswapV2Pair::Swap::decode_log_data(log.data()).ok()?;
+
+        let (token_in, token_out, amount_in, amount_out) =
+            if swap.amount0In > U256::ZERO && swap.amount1Out > U256::ZERO {
+                // Sending token0 to get token1
+                (&pool.token0, &pool.token1, swap.amount0In, swap.amount1Out)
+            } else if swap.amount1In > U256::ZERO && swap.amount0Out > U256::ZERO {
+                // Sending token1 to get token0
+                (&pool.token1, &pool.token0, swap.amount1In, swap.amount0Out)
+            } else {
+                return None; // Invalid swap
+            };
+
+        let (base_amount, quote_amount) = if token_in.address == pool.base().address {
+            (amount_in, amount_out)
+        } else {
+            (amount_out, amount_in)
+        };
+
+        let side = if token_out.address == pool.base().address {
+            Side::Buy
+        } else {
+            Side::Sell
+        };
+
+        let block_timestamp = log.block_timestamp.unwrap_or(Utc::now().timestamp() as u64);
+
+        Some(Self {
+            pool_name: pool_name.to_string(),
+            pair: pool.pair_name(),
+            timestamp: DateTime::from_timestamp(block_timestamp as i64, 0)?,
+            side,
+            token_in: token_in.clone(),
+            token_out: token_out.clone(),
+            amount_in,
+            amount_out,
+            base_amount,
+            price: Price::from_amounts(
+                base_amount,
+                pool.base().decimals,
+                quote_amount,
+                pool.quote().decimals,
+            ),
+            block_number: log.block_number.unwrap_or_default(),
+            tx_hash: log.transaction_hash,
+        })
+    }
+}
+
+impl Trade {
+    /// The trade as a row of the QuestDB `trades` table, in base terms.
+    pub fn to_swap_row(&self, pool: &Pool) -> SwapRow {
+        SwapRow {
+            symbol: self.pair.clone(),
+            side: self.side,
+            price: self.price.to_f64(),
+            amount: token_amount_to_f64(self.base_amount, pool.base().decimals),
+            timestamp: self.timestamp,
+            pool: self.pool_name.clone(),
+            block_number: self.block_number,
+            tx_hash: self.tx_hash,
+        }
+    }
+}
+
+/// A previously printed trade whose block was reorged out.
+pub struct Retraction<'a>(pub &'a Trade);
+
+impl fmt::Display for Retraction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trade = self.0;
+        write!(f, "RETRACTED {} (block {}", trade, trade.block_number)?;
+        if let Some(tx_hash) = trade.tx_hash {
+            write!(f, ", tx {}", tx_hash)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - [{}] {}: {} | {} {} → {} {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.pool_name,
+            self.pair,
+            self.side,
+            format_token_amount(self.amount_in, self.token_in.decimals, TRADE_AMOUNT_STYLE),
+            self.token_in.symbol,
+            format_token_amount(self.amount_out, self.token_out.decimals, TRADE_AMOUNT_STYLE),
+            self.token_out.symbol,
+        )
+    }
+}
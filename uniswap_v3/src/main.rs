@@ -1,103 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+
 use alloy::{
-    primitives::{Address, U256, Uint, address},
+    eips::BlockId,
+    primitives::Address,
     providers::ProviderBuilder,
-    sol,
+    rpc::types::{BlockNumberOrTag, Filter},
+    sol_types::SolEvent,
 };
 use anyhow::Result;
+use clap::Parser;
+use futures_util::future::try_join_all;
+use oracle_core::{
+    ChainConfig, ConfigArgs, Finality, LogUpdate, Pool, PoolConfig, ProtocolVersion, QuestDbSink,
+    SwapWriter, TokenRegistry, UniswapV3Pool, follow_logs, observe_twap, snapshot_pools,
+};
 
-const RPC_URL: &str = "https://mainnet.gateway.tenderly.co";
-
-const POOL_ADDRESS: Address = address!("0x8ad599c3A0ff1De082011EFDDc58f1908eb6e6D8");
-
-sol! {
-    #[sol(rpc)]
-    contract UniswapV3Pool {
-        struct Slot0 {
-            // the current price
-            uint160 sqrtPriceX96;
-            // the current tick
-            int24 tick;
-            // the most-recently updated index of the observations array
-            uint16 observationIndex;
-            // the current maximum number of observations that are being stored
-            uint16 observationCardinality;
-            // the next maximum number of observations to store, triggered in observations.write
-            uint16 observationCardinalityNext;
-            // the current protocol fee as a percentage of the swap fee taken on withdrawal
-            // represented as an integer denominator (1/x)%
-            uint8 feeProtocol;
-            // whether the pool is locked
-            bool unlocked;
-        }
+mod trade;
 
-        function token0() external view returns (address);
-        function token1() external view returns (address);
-
-        function slot0() external view returns (
-            uint160 sqrtPriceX96,
-            int24 tick,
-            uint16 observationIndex,
-            uint16 observationCardinality,
-            uint16 observationCardinalityNext,
-            uint8 feeProtocol,
-            bool unlocked
-        );
-    }
+use trade::{Retraction, Trade};
 
-    #[sol(rpc)]
-    contract ERC20 {
-        function decimals() external view returns (uint8);
-        function symbol() external view returns (string);
-    }
+/// Print the current slot0 price of configured Uniswap V3 pools, optionally
+/// streaming their swaps.
+#[derive(Parser)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Also report the time-weighted average price over this window
+    /// (e.g. 300, 5m, 1h); may be repeated
+    #[arg(long = "twap", value_name = "WINDOW", value_parser = parse_window)]
+    twap_windows: Vec<u32>,
+
+    /// Keep running and print every swap of the selected pools
+    #[arg(long)]
+    stream: bool,
+
+    #[command(flatten)]
+    finality: Finality,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let provider = ProviderBuilder::new().connect(RPC_URL).await?;
+    let cli = Cli::parse();
+    let config = cli.config.load()?;
+    let registry = config.token_registry()?;
+
+    let mut pools_by_chain: BTreeMap<&str, Vec<&PoolConfig>> = BTreeMap::new();
+    for pool_config in cli.config.select_pools(&config, ProtocolVersion::V3)? {
+        pools_by_chain
+            .entry(&pool_config.chain)
+            .or_default()
+            .push(pool_config);
+    }
 
-    let pool_contract = UniswapV3Pool::new(POOL_ADDRESS, &provider);
+    // Only streamed swaps are written to QuestDB
+    let (writer, mut sink) = match (&config.questdb, cli.stream) {
+        (Some(questdb), true) => {
+            let (writer, sink) = QuestDbSink::connect(questdb).await?;
+            (Some(writer), Some(sink))
+        }
+        _ => (None, None),
+    };
+
+    let chains = try_join_all(
+        pools_by_chain
+            .into_iter()
+            .map(|(chain_name, pool_configs)| {
+                run_chain(
+                    &cli,
+                    &config.chains[chain_name],
+                    pool_configs,
+                    &registry,
+                    writer.clone(),
+                )
+            }),
+    );
+    // Only the chains hold writers now, so the sink shuts down with them
+    drop(writer);
 
-    let token0_addr = pool_contract.token0().call().await?;
-    let token1_addr = pool_contract.token1().call().await?;
+    let sink_stopped = async {
+        match &mut sink {
+            Some(sink) => sink.stopped().await,
+            None => std::future::pending().await,
+        }
+    };
 
-    let token0_contract = ERC20::new(token0_addr, &provider);
-    let token1_contract = ERC20::new(token1_addr, &provider);
+    tokio::select! {
+        result = chains => {
+            result?;
+        }
+        result = sink_stopped => return result,
+        _ = tokio::signal::ctrl_c() => eprintln!("Interrupted, flushing pending swaps"),
+    }
 
-    let token0_decimals = token0_contract.decimals().call().await?;
-    let token1_decimals = token1_contract.decimals().call().await?;
+    if let Some(sink) = sink {
+        sink.finish().await?;
+    }
 
-    let token0_symbol = token0_contract.symbol().call().await?;
-    let token1_symbol = token1_contract.symbol().call().await?;
+    Ok(())
+}
 
-    let slot0 = pool_contract.slot0().call().await?;
-    let sqrt_price_x96 = slot0.sqrtPriceX96;
+async fn run_chain(
+    cli: &Cli,
+    chain: &ChainConfig,
+    pool_configs: Vec<&PoolConfig>,
+    registry: &TokenRegistry,
+    writer: Option<SwapWriter>,
+) -> Result<()> {
+    let provider = ProviderBuilder::new().connect(&chain.rpc_url).await?;
+
+    // Bootstrap and slot0 reads are batched through Multicall3
+    let addresses: Vec<_> = pool_configs
+        .iter()
+        .map(|pool_config| (pool_config.address, ProtocolVersion::V3))
+        .collect();
+    let mut pools = Pool::fetch_many(&provider, registry, chain.chain_id, &addresses).await?;
+    // Swaps are stored in the configured orientation, so --invert never
+    // changes what QuestDB holds
+    let mut stored = Vec::with_capacity(pools.len());
+    for (pool_config, pool) in pool_configs.iter().zip(&mut pools) {
+        stored.push(Pool {
+            orientation: pool_config.orientation(pool)?,
+            ..pool.clone()
+        });
+        cli.config.orient(pool_config, pool)?;
+    }
+    let states = snapshot_pools(&provider, &pools, BlockId::latest()).await?;
 
-    let token0_per_token1 = calculate_price(sqrt_price_x96, token0_decimals, token1_decimals);
-    println!(
-        "1 {} = {:.10} {}",
-        token0_symbol, token0_per_token1, token1_symbol
-    );
+    for ((pool_config, pool), state) in pool_configs.iter().zip(&pools).zip(states) {
+        println!("{} ({})", pool_config.name, pool_config.address);
 
-    let token1_per_token0 = 1.0 / token0_per_token1;
-    println!(
-        "1 {} = {:.10} {}",
-        token1_symbol, token1_per_token0, token0_symbol
-    );
+        let Some(state) = state else {
+            println!("slot0() call failed");
+            continue;
+        };
 
-    Ok(())
-}
+        let quote = pool.price_quote(state.price(pool));
+
+        println!("{}", quote);
+        println!("{}", quote.inverse());
+
+        if cli.twap_windows.is_empty() {
+            continue;
+        }
 
-fn calculate_price(sqrt_price_x96: Uint<160, 3>, token0_decimals: u8, token1_decimals: u8) -> f64 {
-    let sqrt_price = U256::from(sqrt_price_x96);
+        for twap in observe_twap(&provider, pool, &cli.twap_windows).await? {
+            let quote = pool.price_quote(twap.price);
+            println!("TWAP {}s (tick {}): {}", twap.window, twap.tick, quote);
+        }
+    }
+
+    if !cli.stream {
+        return Ok(());
+    }
 
-    // price = (sqrtPriceX96 / 2^96)^2
-    let q_96 = U256::from(2).pow(U256::from(96));
-    let sqrt_price_f64 = sqrt_price.to::<u128>() as f64;
-    let q_96_f64 = q_96.to::<u128>() as f64;
-    let price_ratio = (sqrt_price_f64 / q_96_f64).powi(2);
+    let pools: HashMap<Address, (String, Pool, Pool)> = pool_configs
+        .iter()
+        .zip(pools)
+        .zip(stored)
+        .map(|((pool_config, pool), stored)| {
+            (
+                pool_config.address,
+                (pool_config.name.clone(), pool, stored),
+            )
+        })
+        .collect();
+
+    let filter = Filter::new()
+        .address(pools.keys().copied().collect::<Vec<_>>())
+        .event(UniswapV3Pool::Swap::SIGNATURE)
+        .from_block(BlockNumberOrTag::Latest);
+
+    follow_logs(chain, &filter, cli.finality, |updates| {
+        print_updates(&pools, writer.as_ref(), updates)
+    })
+    .await
+}
 
-    let price_adjusted =
-        price_ratio * 10_f64.powi((token0_decimals as i32) - (token1_decimals as i32));
+fn print_updates(
+    pools: &HashMap<Address, (String, Pool, Pool)>,
+    writer: Option<&SwapWriter>,
+    updates: Vec<LogUpdate>,
+) {
+    for update in updates {
+        let (LogUpdate::Emit(log) | LogUpdate::Retract(log)) = &update;
+        let Some((pool_name, pool, stored_pool)) = pools.get(&log.address()) else {
+            continue;
+        };
+        let Some(trade) = Trade::from_log(pool_name, pool, log) else {
+            continue;
+        };
+
+        match update {
+            LogUpdate::Emit(_) => {
+                println!("{}", trade);
+                let stored = writer.zip(Trade::from_log(pool_name, stored_pool, log));
+                if let Some((writer, stored)) = stored {
+                    writer.write(stored.to_swap_row(stored_pool));
+                }
+            }
+            // ILP is append-only, so a retracted swap stays in QuestDB;
+            // --confirmations keeps reorged swaps from being written at all
+            LogUpdate::Retract(_) => println!("{}", Retraction(&trade)),
+        }
+    }
+}
 
-    price_adjusted
+/// Parses a window such as `90`, `90s`, `5m`, `1h` or `1d` into seconds.
+fn parse_window(value: &str) -> Result<u32, String> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+
+    let number: u32 = number
+        .parse()
+        .map_err(|_| format!("invalid window `{}`", value))?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit in window `{}`", value)),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("window `{}` is too long", value))
 }
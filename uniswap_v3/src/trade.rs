@@ -0,0 +1,142 @@
+use std::fmt;
+
+use alloy::{
+    primitives::{B256, I256},
+    rpc::types::Log,
+    sol_types::SolEvent,
+};
+use chrono::{DateTime, Utc};
+use oracle_core::{
+    Pool, Price, Side, SwapRow, TRADE_AMOUNT_STYLE, Token, UniswapV3Pool, calculate_price_v3,
+    format_token_amount, token_amount_to_f64,
+};
+
+/// A decoded V3 `Swap` log tagged with the pool it came from.
+///
+/// Amounts are signed from the pool's point of view: positive amounts were
+/// paid into the pool, negative ones were sent out of it.
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub pool_name: String,
+    pub pair: String,
+    pub timestamp: DateTime<Utc>,
+    pub side: Side,
+    pub base: Token,
+    pub quote: Token,
+    pub base_amount: I256,
+    pub quote_amount: I256,
+    /// Post-swap price, quote per base.
+    pub price: Price,
+    pub tick: i32,
+    pub liquidity: u128,
+    pub block_number: u64,
+    pub tx_hash: Option<B256>,
+}
+
+impl Trade {
+    /// Decodes a V3 `Swap` log of `pool`; `None` if it cannot be decoded.
+    pub fn from_log(pool_name: &str, pool: &Pool, log: &Log) -> Option<Self> {
+        let swap = UniswapV3Pool::Swap::decode_log_data(log.data()).ok()?;
+
+        let (base_amount, quote_amount) = pool.orientation.base_quote(swap.amount0, swap.amount1);
+        let side = if base_amount.is_negative() {
+            Side::Buy
+        } else {
+            Side::Sell
+        };
+
+        let block_timestamp = log.block_timestamp.unwrap_or(Utc::now().timestamp() as u64);
+
+        Some(Self {
+            pool_name: pool_name.to_string(),
+            pair: pool.pair_name(),
+            timestamp: DateTime::from_timestamp(block_timestamp as i64, 0)?,
+            side,
+            base: pool.base().clone(),
+            quote: pool.quote().clone(),
+            base_amount,
+            quote_amount,
+            price: pool.orient_price(calculate_price_v3(
+                swap.sqrtPriceX96,
+                pool.token0.decimals,
+                pool.token1.decimals,
+            )),
+            tick: swap.tick.as_i32(),
+            liquidity: swap.liquidity,
+            block_number: log.block_number.unwrap_or_default(),
+            tx_hash: log.transaction_hash,
+        })
+    }
+}
+
+impl Trade {
+    /// The trade as a row of the QuestDB `trades` table, priced at its
+    /// execution price in quote per base of `pool`, the pool it was decoded
+    /// with.
+    pub fn to_swap_row(&self, pool: &Pool) -> SwapRow {
+        let base_amount = self.base_amount.unsigned_abs();
+        let quote_amount = self.quote_amount.unsigned_abs();
+        let price = Price::from_amounts(
+            base_amount,
+            pool.base().decimals,
+            quote_amount,
+            pool.quote().decimals,
+        );
+
+        SwapRow {
+            symbol: self.pair.clone(),
+            side: self.side,
+            price: price.to_f64(),
+            amount: token_amount_to_f64(base_amount, pool.base().decimals),
+            timestamp: self.timestamp,
+            pool: self.pool_name.clone(),
+            block_number: self.block_number,
+            tx_hash: self.tx_hash,
+        }
+    }
+}
+
+/// A previously printed trade whose block was reorged out.
+pub struct Retraction<'a>(pub &'a Trade);
+
+impl fmt::Display for Retraction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trade = self.0;
+        write!(f, "RETRACTED {} (block {}", trade, trade.block_number)?;
+        if let Some(tx_hash) = trade.tx_hash {
+            write!(f, ", tx {}", tx_hash)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn format_signed_amount(amount: I256, decimals: u8) -> String {
+    let sign = if amount.is_negative() { "-" } else { "+" };
+    format!(
+        "{}{}",
+        sign,
+        format_token_amount(amount.unsigned_abs(), decimals, TRADE_AMOUNT_STYLE)
+    )
+}
+
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - [{}] {}: {} | {} {}, {} {} | 1 {} = {:.10} {} | tick {} | liquidity {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            self.pool_name,
+            self.pair,
+            self.side,
+            format_signed_amount(self.base_amount, self.base.decimals),
+            self.base.symbol,
+            format_signed_amount(self.quote_amount, self.quote.decimals),
+            self.quote.symbol,
+            self.base.symbol,
+            self.price,
+            self.quote.symbol,
+            self.tick,
+            self.liquidity,
+        )
+    }
+}